@@ -0,0 +1,41 @@
+extern crate env_logger;
+extern crate svg2polylines;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process::exit;
+
+use svg2polylines::Polyline;
+use svg2polylines::export::diagnostic::{write_diagnostic, DiagnosticOptions};
+
+fn main() {
+    // Logging
+    env_logger::init();
+
+    // Argument parsing
+    let args: Vec<_> = env::args().collect();
+    match args.len() {
+        2 => {},
+        _ => {
+            println!("Usage: {} <path/to/file.svg> > diagnostic.svg", args[0]);
+            exit(1);
+        },
+    };
+
+    // Load file
+    let mut file = fs::File::open(&args[1]).unwrap();
+    let mut s = String::new();
+    file.read_to_string(&mut s).unwrap();
+
+    // Parse data
+    let polylines: Vec<Polyline> = svg2polylines::parse(&s);
+
+    // Render diagnostic view, starting at the origin
+    let options = DiagnosticOptions {
+        start: Some((0.0, 0.0).into()),
+        ..DiagnosticOptions::default()
+    };
+    let stdout = io::stdout();
+    write_diagnostic(&mut stdout.lock(), &polylines, &options).unwrap();
+}
@@ -0,0 +1,47 @@
+extern crate env_logger;
+extern crate svg2polylines;
+
+use std::env;
+use std::fs;
+use std::io::{BufWriter, Read};
+use std::process::exit;
+
+use svg2polylines::Polyline;
+use svg2polylines::export::raster::{render, RasterOptions};
+
+fn main() {
+    // Logging
+    env_logger::init();
+
+    // Argument parsing
+    let args: Vec<_> = env::args().collect();
+    let scale = match args.len() {
+        3 => 4.0,
+        4 => args[3].parse().unwrap_or_else(|_| {
+            println!("Invalid scale: {}", args[3]);
+            exit(1);
+        }),
+        _ => {
+            println!("Usage: {} <path/to/file.svg> <path/to/output.png> [pixels-per-unit]", args[0]);
+            exit(1);
+        },
+    };
+
+    // Load file
+    let mut file = fs::File::open(&args[1]).unwrap();
+    let mut s = String::new();
+    file.read_to_string(&mut s).unwrap();
+
+    // Parse data
+    let polylines: Vec<Polyline> = svg2polylines::parse(&s);
+
+    // Render and save image
+    let options = RasterOptions {
+        scale,
+        ..RasterOptions::default()
+    };
+    let raster = render(&polylines, &options);
+    let mut out = BufWriter::new(fs::File::create(&args[2]).unwrap());
+    raster.write_png(&mut out).unwrap();
+    println!("Rendered {} polylines into {}x{} pixels.", polylines.len(), raster.width(), raster.height());
+}
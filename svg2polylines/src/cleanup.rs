@@ -0,0 +1,149 @@
+//! Remove degenerate geometry from polylines.
+//!
+//! Flattening path data produces duplicate points for sequences like
+//! `M 10,10 L 10,10` or repeated `Z` commands, and straight path segments
+//! that were split into collinear pieces. This pass removes such points as
+//! well as polylines that don't draw anything.
+
+use {CoordinatePair, Polyline};
+use util::{distance, is_closed, segment_distance};
+
+/// Options for the cleanup pass.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CleanupOptions {
+    /// Points closer than this are considered equal, and points deviating
+    /// less than this from a straight line are considered collinear.
+    pub epsilon: f64,
+    /// Whether to remove interior points lying on the straight line between
+    /// their neighbours.
+    pub remove_collinear: bool,
+    /// Whether to remove closed polylines that don't enclose any area, i.e.
+    /// that only trace a line back and forth.
+    pub remove_zero_area: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        CleanupOptions {
+            epsilon: 1e-6,
+            remove_collinear: true,
+            remove_zero_area: true,
+        }
+    }
+}
+
+/// Whether all points of the closed polyline lie within `epsilon` of a
+/// single line.
+fn is_zero_area(polyline: &[CoordinatePair], epsilon: f64) -> bool {
+    let first = polyline[0];
+    let furthest = polyline.iter()
+        .fold(first, |best, p| if distance(&first, p) > distance(&first, &best) { *p } else { best });
+    polyline.iter().all(|p| segment_distance(p, &first, &furthest) <= epsilon)
+}
+
+/// Clean up a single polyline. Returns `None` if nothing is left to draw.
+pub fn cleanup_polyline(polyline: &[CoordinatePair], options: &CleanupOptions) -> Option<Polyline> {
+    let closed = is_closed(polyline);
+    let mut result: Polyline = Vec::with_capacity(polyline.len());
+    for p in polyline {
+        if result.last().is_some_and(|last| distance(last, p) <= options.epsilon) {
+            continue;
+        }
+        if options.remove_collinear {
+            while result.len() >= 2 {
+                let (a, b) = (result[result.len() - 2], result[result.len() - 1]);
+                if segment_distance(&b, &a, p) <= options.epsilon {
+                    result.pop();
+                } else {
+                    break;
+                }
+            }
+        }
+        result.push(*p);
+    }
+
+    // Keep closed polylines exactly closed, even if the original closing
+    // point was only within epsilon of the start
+    if closed && result.len() > 1 {
+        let last = result.len() - 1;
+        result[last] = result[0];
+    }
+
+    if result.len() < 2 {
+        return None;
+    }
+    if options.remove_zero_area && closed && is_zero_area(&result, options.epsilon) {
+        return None;
+    }
+    Some(result)
+}
+
+/// Clean up all polylines, dropping the ones that don't draw anything.
+pub fn cleanup(polylines: Vec<Polyline>, options: &CleanupOptions) -> Vec<Polyline> {
+    polylines.iter()
+        .filter_map(|p| cleanup_polyline(p, options))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_duplicate_points() {
+        let line = polyline(&[(0.0, 0.0), (0.0, 0.0), (1.0, 1.0), (1.0, 1.0 + 1e-9), (2.0, 0.0)]);
+        assert_eq!(
+            cleanup_polyline(&line, &CleanupOptions::default()),
+            Some(polyline(&[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)])),
+        );
+    }
+
+    #[test]
+    fn test_collinear_points() {
+        let line = polyline(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 3.0)]);
+        let options = CleanupOptions::default();
+        assert_eq!(
+            cleanup_polyline(&line, &options),
+            Some(polyline(&[(0.0, 0.0), (2.0, 0.0), (2.0, 3.0)])),
+        );
+
+        // Reversing direction is not collinear
+        let spike = polyline(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.0)]);
+        assert_eq!(cleanup_polyline(&spike, &options), Some(spike.clone()));
+
+        let options = CleanupOptions { remove_collinear: false, ..CleanupOptions::default() };
+        assert_eq!(cleanup_polyline(&line, &options), Some(line));
+    }
+
+    #[test]
+    fn test_closed_polylines() {
+        let options = CleanupOptions::default();
+
+        // Repeated close path
+        let square = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0), (0.0, 0.0)]);
+        assert_eq!(
+            cleanup_polyline(&square, &options),
+            Some(polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)])),
+        );
+
+        // Back and forth along a line
+        let flat = polyline(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (0.0, 0.0)]);
+        assert_eq!(cleanup_polyline(&flat, &options), None);
+        let options = CleanupOptions { remove_zero_area: false, ..CleanupOptions::default() };
+        assert_eq!(cleanup_polyline(&flat, &options), Some(polyline(&[(0.0, 0.0), (2.0, 0.0), (0.0, 0.0)])));
+    }
+
+    #[test]
+    fn test_cleanup_drops_single_points() {
+        let polylines = vec![
+            polyline(&[(10.0, 10.0), (10.0, 10.0)]),
+            polyline(&[(0.0, 0.0), (1.0, 0.0)]),
+            polyline(&[(5.0, 5.0), (5.0, 5.0), (5.0, 5.0), (5.0, 5.0)]),
+        ];
+        assert_eq!(cleanup(polylines, &CleanupOptions::default()), vec![polyline(&[(0.0, 0.0), (1.0, 0.0)])]);
+    }
+}
@@ -0,0 +1,163 @@
+//! Clip polylines to a rectangle or polygon.
+//!
+//! Everything outside of the clip region is removed, e.g. to keep a plot on
+//! the paper and away from the clamps of the plotter. Polylines are split
+//! where they leave the clip region, but their order and direction are
+//! preserved.
+
+use {CoordinatePair, FillRule, Polyline};
+use util::{distance, intersection, is_closed, Segment, SegmentGrid, WindingIndex};
+
+/// Keep the parts of the polylines that are inside of the region enclosed by
+/// `clip` if `keep_inside` is set, or outside of it otherwise.
+fn split(polylines: Vec<Polyline>, clip: &[Polyline], fill_rule: FillRule, keep_inside: bool) -> Vec<Polyline> {
+    let edges: Vec<Segment> = clip.iter()
+        .filter(|p| p.len() > 2)
+        .flat_map(|p| (0..p.len()).map(move |i| (p[i], p[(i + 1) % p.len()])))
+        .filter(|&(a, b)| a != b)
+        .collect();
+    if edges.is_empty() {
+        return if keep_inside { Vec::new() } else { polylines };
+    }
+    let total_length: f64 = edges.iter().map(|&(a, b)| distance(&a, &b)).sum();
+    let mut grid = SegmentGrid::new(total_length / edges.len() as f64);
+    for &(a, b) in &edges {
+        grid.insert(a, b);
+    }
+    let index = WindingIndex::new(&edges);
+    let keep = |p: &CoordinatePair| fill_rule.is_inside(index.winding(p)) == keep_inside;
+
+    let mut result = Vec::with_capacity(polylines.len());
+    for polyline in &polylines {
+        if polyline.len() == 1 {
+            if keep(&polyline[0]) {
+                result.push(polyline.clone());
+            }
+            continue;
+        }
+        let mut pieces: Vec<Polyline> = Vec::new();
+        // Whether the last piece ends at the current point
+        let mut connected = false;
+        for w in polyline.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let point_at = |t: f64| if t == 1.0 {
+                b
+            } else {
+                CoordinatePair::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+            };
+            let mut splits: Vec<f64> = grid.near(&a, &b).into_iter()
+                .filter_map(|i| {
+                    let (c, d) = grid.segment(i);
+                    intersection(&a, &b, &c, &d)
+                        .filter(|&(t, u)| t > 0.0 && t < 1.0 && (0.0..=1.0).contains(&u))
+                        .map(|(t, _)| t)
+                })
+                .collect();
+            splits.push(0.0);
+            splits.push(1.0);
+            splits.sort_by(|x, y| x.total_cmp(y));
+            splits.dedup();
+
+            for s in splits.windows(2) {
+                let (start, end) = (s[0], s[1]);
+                if !keep(&point_at((start + end) / 2.0)) {
+                    connected = false;
+                    continue;
+                }
+                match pieces.last_mut() {
+                    Some(piece) if connected => piece.push(point_at(end)),
+                    _ => pieces.push(vec![point_at(start), point_at(end)]),
+                }
+                connected = true;
+            }
+        }
+
+        // A closed polyline that starts in the kept region is drawn from where
+        // it enters the region around to where it leaves it
+        if is_closed(polyline) && connected && pieces.len() > 1 && pieces[0][0] == polyline[0] {
+            let first = pieces.remove(0);
+            pieces.last_mut().unwrap().extend(first.into_iter().skip(1));
+        }
+        result.extend(pieces);
+    }
+    result
+}
+
+/// Clip the polylines to the region enclosed by the closed polylines of
+/// `clip`, according to the fill rule. Like in SVG, open clip polylines are
+/// implicitly closed.
+pub fn clip_to_polygon(polylines: Vec<Polyline>, clip: &[Polyline], fill_rule: FillRule) -> Vec<Polyline> {
+    split(polylines, clip, fill_rule, true)
+}
+
+/// Remove the parts of the polylines inside of the region enclosed by the
+/// polylines of `clip`, the opposite of `clip_to_polygon`.
+pub fn remove_inside(polylines: Vec<Polyline>, clip: &[Polyline], fill_rule: FillRule) -> Vec<Polyline> {
+    split(polylines, clip, fill_rule, false)
+}
+
+/// Clip the polylines to the axis aligned rectangle from `min` to `max`.
+pub fn clip_to_rect(polylines: Vec<Polyline>, min: CoordinatePair, max: CoordinatePair) -> Vec<Polyline> {
+    let rect = vec![
+        min,
+        CoordinatePair::new(max.x, min.y),
+        max,
+        CoordinatePair::new(min.x, max.y),
+        min,
+    ];
+    clip_to_polygon(polylines, &[rect], FillRule::NonZero)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_clip_to_rect() {
+        let polylines = vec![
+            polyline(&[(-5.0, 5.0), (15.0, 5.0)]),
+            polyline(&[(20.0, 20.0), (30.0, 30.0)]),
+            polyline(&[(2.0, 2.0), (8.0, 2.0), (8.0, 8.0)]),
+            // Leaves and enters again
+            polyline(&[(5.0, 8.0), (5.0, 12.0), (7.0, 12.0), (7.0, 8.0)]),
+        ];
+        let result = clip_to_rect(polylines, (0.0, 0.0).into(), (10.0, 10.0).into());
+        assert_eq!(result, vec![
+            polyline(&[(0.0, 5.0), (10.0, 5.0)]),
+            polyline(&[(2.0, 2.0), (8.0, 2.0), (8.0, 8.0)]),
+            polyline(&[(5.0, 8.0), (5.0, 10.0)]),
+            polyline(&[(7.0, 10.0), (7.0, 8.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_clip_closed_polyline() {
+        // A square sticking out to the right, starting inside
+        let square = polyline(&[(5.0, 2.0), (15.0, 2.0), (15.0, 8.0), (5.0, 8.0), (5.0, 2.0)]);
+        let result = clip_to_rect(vec![square], (0.0, 0.0).into(), (10.0, 10.0).into());
+        assert_eq!(result, vec![polyline(&[(10.0, 8.0), (5.0, 8.0), (5.0, 2.0), (10.0, 2.0)])]);
+    }
+
+    #[test]
+    fn test_clip_to_polygon_with_hole() {
+        let outer = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+        let hole = polyline(&[(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0), (4.0, 4.0)]);
+        let line = polyline(&[(-1.0, 5.0), (11.0, 5.0)]);
+        let result = clip_to_polygon(vec![line.clone()], &[outer.clone(), hole.clone()], FillRule::EvenOdd);
+        assert_eq!(result, vec![polyline(&[(0.0, 5.0), (4.0, 5.0)]), polyline(&[(6.0, 5.0), (10.0, 5.0)])]);
+        // With nonzero, the equally oriented inner square is not a hole
+        let result = clip_to_polygon(vec![line.clone()], &[outer.clone(), hole.clone()], FillRule::NonZero);
+        assert_eq!(result.len(), 1);
+
+        let result = remove_inside(vec![line], &[outer, hole], FillRule::EvenOdd);
+        assert_eq!(result, vec![
+            polyline(&[(-1.0, 5.0), (0.0, 5.0)]),
+            polyline(&[(4.0, 5.0), (6.0, 5.0)]),
+            polyline(&[(10.0, 5.0), (11.0, 5.0)]),
+        ]);
+    }
+}
@@ -0,0 +1,235 @@
+//! Fill closed shapes with concentric contours.
+//!
+//! Instead of straight hatch lines, the shape is filled by repeatedly
+//! insetting its outline, which looks better for laser engraving and marker
+//! based fills. Holes grow and shapes split into several islands as they are
+//! inset, the same way a physical tool would move.
+//!
+//! Optionally, nested contours are linked into a spiral, so that they can be
+//! drawn without lifting the pen.
+
+use {CoordinatePair, FillRule, Polyline, Shape};
+use offset::{offset_outline, outline, OffsetOptions};
+use util::{bounds, closest_point, distance, segment_distance, signed_area, winding};
+
+/// Options for contour fills.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ContourOptions {
+    /// The distance between contours. The first contour is inset by this
+    /// distance from the outline.
+    pub spacing: f64,
+    /// The maximum deviation of rounded corners from a true arc.
+    pub tolerance: f64,
+    /// Whether to link nested contours into a spiral.
+    pub spiral: bool,
+}
+
+impl Default for ContourOptions {
+    fn default() -> Self {
+        ContourOptions {
+            spacing: 1.0,
+            tolerance: 0.01,
+            spiral: false,
+        }
+    }
+}
+
+/// Return a copy of the closed polyline that starts and ends at the point of
+/// the polyline closest to `p`.
+fn start_closest_to(ring: &[CoordinatePair], p: &CoordinatePair) -> Polyline {
+    let (i, _) = ring.windows(2)
+        .map(|w| segment_distance(p, &w[0], &w[1]))
+        .enumerate()
+        .fold((0, f64::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best });
+    let start = closest_point(p, &ring[i], &ring[i + 1]);
+    let mut rotated = vec![start];
+    rotated.extend(ring[i + 1..ring.len() - 1].iter().chain(&ring[..i + 1]).filter(|&&q| q != start));
+    rotated.push(start);
+    rotated
+}
+
+/// Shorten a polyline by removing `length` from its end.
+fn shorten(polyline: &mut Polyline, mut length: f64) {
+    while polyline.len() > 1 {
+        let (a, b) = (polyline[polyline.len() - 2], polyline[polyline.len() - 1]);
+        let segment = distance(&a, &b);
+        if segment > length {
+            let t = length / segment;
+            let last = polyline.len() - 1;
+            polyline[last] = CoordinatePair::new(b.x + (a.x - b.x) * t, b.y + (a.y - b.y) * t);
+            return;
+        }
+        length -= segment;
+        polyline.pop();
+    }
+}
+
+/// Link nested closed contours (from the outside in) into a single spiral.
+///
+/// Every contour but the last is cut short by the spacing and connected to
+/// the next contour at the point closest to its start.
+fn link_spiral(contours: Vec<Polyline>, spacing: f64) -> Polyline {
+    let count = contours.len();
+    let mut spiral: Polyline = Vec::new();
+    let mut previous_start = None;
+    for (i, contour) in contours.into_iter().enumerate() {
+        let mut contour = match previous_start {
+            Some(p) => start_closest_to(&contour, &p),
+            None => contour,
+        };
+        previous_start = Some(contour[0]);
+        let perimeter: f64 = contour.windows(2).map(|w| distance(&w[0], &w[1])).sum();
+        if i + 1 < count && perimeter > 2.0 * spacing {
+            shorten(&mut contour, spacing);
+        }
+        spiral.extend(contour);
+    }
+    spiral
+}
+
+/// Group the contours of consecutive levels into chains that can be linked
+/// into a spiral: a contour is appended to the chain of the contour enclosing
+/// it if it is the only contour there.
+fn spiral_chains(levels: Vec<Vec<Polyline>>) -> Vec<Vec<Polyline>> {
+    let mut chains: Vec<Vec<Polyline>> = Vec::new();
+    // The chain index of every contour of the previous level
+    let mut previous: Vec<(Polyline, usize)> = Vec::new();
+    for level in levels {
+        // Find the innermost contour of the previous level enclosing each
+        // contour of this level
+        let parents: Vec<Option<usize>> = level.iter()
+            .map(|contour| {
+                previous.iter().enumerate()
+                    .filter(|(_, (parent, _))| {
+                        let edges: Vec<_> = parent.windows(2).map(|w| (w[0], w[1])).collect();
+                        winding(edges.iter(), &contour[0]) != 0
+                    })
+                    .min_by(|a, b| signed_area(&(a.1).0).abs().total_cmp(&signed_area(&(b.1).0).abs()))
+                    .map(|(i, _)| i)
+            })
+            .collect();
+        let mut current = Vec::with_capacity(level.len());
+        for (contour, parent) in level.into_iter().zip(parents.iter()) {
+            let only_child = parent.is_some_and(|p| parents.iter().filter(|&&q| q == Some(p)).count() == 1);
+            let chain = match *parent {
+                Some(p) if only_child => previous[p].1,
+                _ => {
+                    chains.push(Vec::new());
+                    chains.len() - 1
+                },
+            };
+            chains[chain].push(contour.clone());
+            current.push((contour, chain));
+        }
+        previous = current;
+    }
+    chains
+}
+
+/// Fill the area enclosed by the polylines with concentric contours.
+///
+/// All polylines together form a single shape, so that for example a
+/// polyline inside another one is a hole if the fill rule says so. The
+/// contours are returned from the outside in.
+pub fn contour(polylines: &[Polyline], fill_rule: FillRule, options: &ContourOptions) -> Vec<Polyline> {
+    if options.spacing <= 0.0 {
+        return Vec::new();
+    }
+    let outline = outline(polylines, fill_rule);
+    let max_levels = match bounds(outline.iter().flat_map(|p| p.iter())) {
+        Some((min, max)) => ((max.x - min.x).max(max.y - min.y) / options.spacing).ceil() as usize,
+        None => return Vec::new(),
+    };
+
+    let offset_options = OffsetOptions { tolerance: options.tolerance, ..OffsetOptions::default() };
+    let mut levels = Vec::new();
+    for level in 1..max_levels + 1 {
+        let contours = offset_outline(&outline, -(level as f64) * options.spacing, &offset_options);
+        if contours.is_empty() {
+            break;
+        }
+        levels.push(contours);
+    }
+
+    if options.spiral {
+        spiral_chains(levels).into_iter()
+            .map(|chain| link_spiral(chain, options.spacing))
+            .collect()
+    } else {
+        levels.into_iter().flat_map(|level| level.into_iter()).collect()
+    }
+}
+
+/// Fill a shape with concentric contours, using its fill rule.
+pub fn contour_shape(shape: &Shape, options: &ContourOptions) -> Vec<Polyline> {
+    contour(&shape.polylines, shape.fill_rule, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::is_closed;
+
+    fn square(min: f64, max: f64) -> Polyline {
+        vec![(min, min).into(), (max, min).into(), (max, max).into(), (min, max).into(), (min, min).into()]
+    }
+
+    #[test]
+    fn test_contour_square() {
+        let contours = contour(&[square(0.0, 10.0)], FillRule::NonZero, &ContourOptions::default());
+        assert_eq!(contours.len(), 4);
+        for (i, c) in contours.iter().enumerate() {
+            assert!(is_closed(c));
+            let side = 10.0 - 2.0 * (i + 1) as f64;
+            assert!((signed_area(c).abs() - side * side).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_contour_with_hole() {
+        let mut hole = square(4.0, 6.0);
+        hole.reverse();
+        let options = ContourOptions { spacing: 0.6, ..ContourOptions::default() };
+        let contours = contour(&[square(0.0, 10.0), hole], FillRule::NonZero, &options);
+        // Outer and hole contours until they meet at a distance of about 2.3
+        assert_eq!(contours.len(), 2 * 3);
+        for c in &contours {
+            assert!(is_closed(c));
+            for p in c {
+                let inside_hole = p.x > 4.0 && p.x < 6.0 && p.y > 4.0 && p.y < 6.0;
+                assert!(!inside_hole);
+            }
+        }
+    }
+
+    #[test]
+    fn test_contour_spiral() {
+        let options = ContourOptions { spiral: true, ..ContourOptions::default() };
+        let spiral = contour(&[square(0.0, 10.0)], FillRule::NonZero, &options);
+        assert_eq!(spiral.len(), 1);
+        let spiral = &spiral[0];
+        // Starts on the outermost and ends on the innermost contour
+        let on_square = |p: &CoordinatePair, min: f64, max: f64| {
+            square(min, max).windows(2).any(|w| segment_distance(p, &w[0], &w[1]) < 1e-9)
+        };
+        assert!(on_square(&spiral[0], 1.0, 9.0));
+        assert!(on_square(&spiral[spiral.len() - 1], 4.0, 6.0));
+        // Each contour but the last is cut short, and connected by a short
+        // step inwards
+        let length: f64 = spiral.windows(2).map(|w| distance(&w[0], &w[1])).sum();
+        let contours = 32.0 + 24.0 + 16.0 + 8.0;
+        assert!(length > contours - 3.0 && length < contours - 3.0 + 3.0 * 2.0_f64.sqrt() + 1e-9, "{}", length);
+    }
+
+    #[test]
+    fn test_shorten_and_start_closest_to() {
+        let mut line: Polyline = square(0.0, 2.0);
+        shorten(&mut line, 3.0);
+        assert_eq!(line, vec![(0.0, 0.0).into(), (2.0, 0.0).into(), (2.0, 2.0).into(), (1.0, 2.0).into()]);
+
+        let rotated = start_closest_to(&square(0.0, 2.0), &(3.0, 1.0).into());
+        assert_eq!(rotated, vec![
+            (2.0, 1.0).into(), (2.0, 2.0).into(), (0.0, 2.0).into(), (0.0, 0.0).into(), (2.0, 0.0).into(), (2.0, 1.0).into(),
+        ]);
+    }
+}
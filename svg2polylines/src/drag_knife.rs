@@ -0,0 +1,136 @@
+//! Path compensation for drag knives.
+//!
+//! The tip of a drag knife (as used by vinyl cutters and plotters with a
+//! cutting head) trails behind the axis of its shaft, like a caster wheel.
+//! Moving the shaft along the polylines would cut rounded corners, so the
+//! shaft is moved ahead of the tip instead: along straight segments, it is
+//! shifted by the blade offset in the cutting direction, and at corners, it
+//! is swiveled around the corner point to turn the blade into the new
+//! direction.
+
+use {CoordinatePair, Polyline};
+use util::{distance, is_closed};
+
+/// Options for drag knife compensation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DragKnifeOptions {
+    /// The distance between the blade tip and the axis of the shaft.
+    pub offset: f64,
+    /// The minimal change of direction at a vertex, in degrees, at which the
+    /// blade is swiveled. Below this, the blade follows on its own.
+    pub corner_angle: f64,
+    /// The maximum deviation of swivel arcs from a true arc.
+    pub tolerance: f64,
+}
+
+impl Default for DragKnifeOptions {
+    fn default() -> Self {
+        DragKnifeOptions {
+            offset: 0.25,
+            corner_angle: 10.0,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Return the shaft position for the tip at `p`, with the blade pointing in
+/// the unit direction `d`.
+fn shaft(p: &CoordinatePair, d: (f64, f64), offset: f64) -> CoordinatePair {
+    CoordinatePair::new(p.x + d.0 * offset, p.y + d.1 * offset)
+}
+
+/// Compensate a single polyline, see `compensate`.
+pub fn compensate_polyline(polyline: &[CoordinatePair], options: &DragKnifeOptions) -> Polyline {
+    let mut points: Polyline = Vec::with_capacity(polyline.len());
+    for p in polyline {
+        if points.last() != Some(p) {
+            points.push(*p);
+        }
+    }
+    if points.len() < 2 || options.offset <= 0.0 {
+        return points;
+    }
+    let directions: Vec<(f64, f64)> = points.windows(2)
+        .map(|w| {
+            let length = distance(&w[0], &w[1]);
+            ((w[1].x - w[0].x) / length, (w[1].y - w[0].y) / length)
+        })
+        .collect();
+    let offset = options.offset;
+    let max_step = 2.0 * (1.0 - (options.tolerance / offset).min(1.0)).acos();
+
+    // Swivel around the tip at `p` from direction `from` to `to`
+    let swivel = |result: &mut Polyline, p: &CoordinatePair, from: (f64, f64), to: (f64, f64)| {
+        let turn = (from.0 * to.1 - from.1 * to.0).atan2(from.0 * to.0 + from.1 * to.1);
+        if turn.abs().to_degrees() >= options.corner_angle {
+            let start = from.1.atan2(from.0);
+            let steps = (turn.abs() / max_step.max(1e-3)).ceil().max(1.0) as usize;
+            for k in 1..steps {
+                let angle = start + turn * k as f64 / steps as f64;
+                result.push(shaft(p, (angle.cos(), angle.sin()), offset));
+            }
+        }
+        let end = shaft(p, to, offset);
+        if result.last() != Some(&end) {
+            result.push(end);
+        }
+    };
+
+    let mut result = vec![shaft(&points[0], directions[0], offset)];
+    for i in 1..points.len() - 1 {
+        result.push(shaft(&points[i], directions[i - 1], offset));
+        swivel(&mut result, &points[i], directions[i - 1], directions[i]);
+    }
+    let last = points.len() - 1;
+    result.push(shaft(&points[last], directions[last - 1], offset));
+    // A closed polyline ends with the blade turned back into the direction
+    // it started with
+    if is_closed(&points) {
+        swivel(&mut result, &points[last], directions[last - 1], directions[0]);
+    }
+    result
+}
+
+/// Compensate the polylines for the blade offset of a drag knife, so that
+/// the blade tip follows the polylines when the shaft follows the result.
+///
+/// The blade is assumed to point in the direction of the first segment at
+/// the start of every polyline.
+pub fn compensate(polylines: Vec<Polyline>, options: &DragKnifeOptions) -> Vec<Polyline> {
+    polylines.iter().map(|p| compensate_polyline(p, options)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_straight_line() {
+        let options = DragKnifeOptions { offset: 1.0, ..DragKnifeOptions::default() };
+        let result = compensate(vec![polyline(&[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0)])], &options);
+        assert_eq!(result, vec![polyline(&[(1.0, 0.0), (6.0, 0.0), (11.0, 0.0)])]);
+    }
+
+    #[test]
+    fn test_corners() {
+        let options = DragKnifeOptions { offset: 1.0, ..DragKnifeOptions::default() };
+        let square = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+        let result = compensate_polyline(&square, &options);
+        // Closed polylines stay closed
+        assert_eq!(result[0], (1.0, 0.0).into());
+        assert_eq!(result[result.len() - 1], (1.0, 0.0).into());
+        // Swivel arcs around every corner
+        for corner in &square[..4] {
+            let on_arc = result.iter().filter(|p| (distance(p, corner) - 1.0).abs() < 1e-9).count();
+            assert!(on_arc > 3, "{:?}", corner);
+        }
+
+        // Slight turns don't need a swivel
+        let bend = polyline(&[(0.0, 0.0), (10.0, 0.0), (20.0, 1.0)]);
+        assert_eq!(compensate_polyline(&bend, &options).len(), 4);
+    }
+}
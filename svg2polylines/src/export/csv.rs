@@ -0,0 +1,41 @@
+//! Export polylines as CSV.
+//!
+//! Every coordinate pair is written as one row with the columns `polyline`
+//! (the index of the polyline), `point` (the index of the coordinate pair
+//! within the polyline), `x` and `y`. The first row contains the column
+//! names.
+
+use std::io::{self, Write};
+
+use Polyline;
+use super::format_number;
+
+/// Write the polylines as CSV to `out`, with `precision` decimal places.
+pub fn write_csv<W: Write>(out: &mut W, polylines: &[Polyline], precision: usize) -> io::Result<()> {
+    writeln!(out, "polyline,point,x,y")?;
+    for (i, polyline) in polylines.iter().enumerate() {
+        for (j, p) in polyline.iter().enumerate() {
+            writeln!(out, "{},{},{},{}", i, j, format_number(p.x, precision), format_number(p.y, precision))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let polylines = vec![
+            vec![(0.0, 0.0).into(), (1.25, -2.0).into()],
+            vec![(3.0, 4.0).into(), (5.0, 6.0).into(), (7.0, 8.0).into()],
+        ];
+        let mut out = Vec::new();
+        write_csv(&mut out, &polylines, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "polyline,point,x,y\n0,0,0,0\n0,1,1.2,-2\n1,0,3,4\n1,1,5,6\n1,2,7,8\n",
+        );
+    }
+}
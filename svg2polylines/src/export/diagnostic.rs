@@ -0,0 +1,184 @@
+//! Render a diagnostic SVG view of the drawing order of polylines.
+//!
+//! Besides the polylines themselves, the view shows the pen-up travel moves
+//! between consecutive polylines as dashed lines, marks the start point of
+//! every polyline, indicates the drawing direction with an arrow and labels
+//! every polyline with its position in the drawing order.
+
+use std::io::{self, Write};
+
+use {Color, CoordinatePair, Polyline};
+use super::svg::{SvgOptions, SvgWriter};
+
+/// Options for the diagnostic view.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiagnosticOptions {
+    /// Options for the underlying SVG document (size, precision, stroke).
+    pub svg: SvgOptions,
+    /// The position of the pen before the first polyline. If set, the travel
+    /// move to the first polyline is drawn as well.
+    pub start: Option<CoordinatePair>,
+    /// The color of the travel moves.
+    pub travel_color: Color,
+    /// The color of start point markers, arrows and labels.
+    pub marker_color: Color,
+    /// The size of start point markers, arrows and labels in user units.
+    pub marker_size: f64,
+    /// Whether to draw an arrow showing the drawing direction.
+    pub arrows: bool,
+    /// Whether to label polylines with their position in the drawing order.
+    pub numbers: bool,
+}
+
+impl Default for DiagnosticOptions {
+    fn default() -> Self {
+        DiagnosticOptions {
+            svg: SvgOptions::default(),
+            start: None,
+            travel_color: Color::new(255, 0, 0),
+            marker_color: Color::new(0, 0, 255),
+            marker_size: 1.0,
+            arrows: true,
+            numbers: true,
+        }
+    }
+}
+
+/// Return the point at half the length of the polyline, together with the
+/// unit direction vector of the segment it lies on.
+fn midpoint(polyline: &[CoordinatePair]) -> Option<(CoordinatePair, (f64, f64))> {
+    let segment_length = |w: &[CoordinatePair]| (w[1].x - w[0].x).hypot(w[1].y - w[0].y);
+    let total: f64 = polyline.windows(2).map(segment_length).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut remaining = total / 2.0;
+    for w in polyline.windows(2) {
+        let length = segment_length(w);
+        if length > 0.0 && remaining <= length {
+            let (dx, dy) = ((w[1].x - w[0].x) / length, (w[1].y - w[0].y) / length);
+            let point = CoordinatePair::new(w[0].x + dx * remaining, w[0].y + dy * remaining);
+            return Some((point, (dx, dy)));
+        }
+        remaining -= length;
+    }
+    None
+}
+
+/// Write a diagnostic view of the polylines, in drawing order, to `out`.
+pub fn write_diagnostic<W: Write>(out: &mut W, polylines: &[Polyline], options: &DiagnosticOptions) -> io::Result<()> {
+    let polylines: Vec<&Polyline> = polylines.iter().filter(|p| !p.is_empty()).collect();
+    let mut writer = SvgWriter { out, options: &options.svg };
+    writer.start(polylines.iter().flat_map(|p| p.iter()).chain(options.start.iter()))?;
+
+    // Travel moves
+    let mut position = options.start;
+    let mut travel = Vec::new();
+    for polyline in &polylines {
+        if let Some(from) = position {
+            travel.push(vec![from, polyline[0]]);
+        }
+        position = polyline.last().cloned();
+    }
+    writeln!(
+        writer.out,
+        r#"<g id="travel" fill="none" stroke="{}" stroke-width="{}" stroke-dasharray="{} {}">"#,
+        options.travel_color.to_hex(),
+        writer.number(options.svg.stroke_width / 2.0),
+        writer.number(options.marker_size),
+        writer.number(options.marker_size / 2.0),
+    )?;
+    for line in travel.iter().filter(|l| l[0] != l[1]) {
+        writer.polyline(line, "")?;
+    }
+    writer.end_group()?;
+
+    // Polylines
+    writer.start_group(Some("polylines"), options.svg.stroke)?;
+    for polyline in &polylines {
+        writer.polyline(polyline, "")?;
+    }
+    writer.end_group()?;
+
+    // Start points, arrows and labels
+    let size = options.marker_size;
+    writeln!(
+        writer.out,
+        r#"<g id="markers" fill="{}" stroke="none" font-family="sans-serif" font-size="{}">"#,
+        options.marker_color.to_hex(),
+        writer.number(size * 1.5),
+    )?;
+    for (i, polyline) in polylines.iter().enumerate() {
+        let start = polyline[0];
+        writeln!(
+            writer.out,
+            r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+            writer.number(start.x), writer.number(start.y), writer.number(size / 3.0),
+        )?;
+        if options.arrows {
+            if let Some((tip, (dx, dy))) = midpoint(polyline) {
+                let base = CoordinatePair::new(tip.x - dx * size, tip.y - dy * size);
+                let (nx, ny) = (-dy * size / 2.0, dx * size / 2.0);
+                writeln!(
+                    writer.out,
+                    r#"<polygon points="{} {} {}"/>"#,
+                    writer.point(&tip),
+                    writer.point(&CoordinatePair::new(base.x + nx, base.y + ny)),
+                    writer.point(&CoordinatePair::new(base.x - nx, base.y - ny)),
+                )?;
+            }
+        }
+        if options.numbers {
+            writeln!(
+                writer.out,
+                r#"<text x="{}" y="{}">{}</text>"#,
+                writer.number(start.x + size / 2.0), writer.number(start.y - size / 2.0), i + 1,
+            )?;
+        }
+    }
+    writer.end_group()?;
+
+    writer.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midpoint() {
+        let line: Polyline = vec![(0.0, 0.0).into(), (2.0, 0.0).into(), (2.0, 4.0).into()];
+        let (point, direction) = midpoint(&line).unwrap();
+        assert_eq!(point, (2.0, 1.0).into());
+        assert_eq!(direction, (0.0, 1.0));
+        assert_eq!(midpoint(&[(1.0, 1.0).into(), (1.0, 1.0).into()]), None);
+    }
+
+    #[test]
+    fn test_write_diagnostic() {
+        let polylines = vec![
+            vec![(0.0, 0.0).into(), (10.0, 0.0).into()],
+            vec![(10.0, 0.0).into(), (10.0, 10.0).into()],
+            vec![(0.0, 10.0).into(), (0.0, 5.0).into()],
+        ];
+        let options = DiagnosticOptions {
+            start: Some((0.0, 0.0).into()),
+            ..DiagnosticOptions::default()
+        };
+        let mut out = Vec::new();
+        write_diagnostic(&mut out, &polylines, &options).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+
+        // Only the single non-zero travel move is drawn
+        let travel_start = svg.find(r#"<g id="travel""#).unwrap();
+        let travel_end = travel_start + svg[travel_start..].find("</g>").unwrap();
+        let travel = &svg[travel_start..travel_end];
+        assert!(travel.contains("stroke-dasharray"));
+        assert_eq!(travel.matches("<polyline").count(), 1);
+        assert!(travel.contains(r#"<polyline points="10,10 0,10"/>"#));
+
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        assert!(svg.contains(">3</text>"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Export polylines as an AutoCAD R2000 ASCII DXF file.
+//!
+//! Every polyline is written as an `LWPOLYLINE` entity. Closed polylines
+//! (where the last coordinate pair equals the first one) are written without
+//! the duplicated end point and with the closed flag set instead.
+//!
+//! Coordinates are written unchanged, and the header declares them as
+//! millimeters (`$INSUNITS` and `$MEASUREMENT`, which R12 files do not have).
+//! Since SVG uses a downward pointing y axis while DXF uses an upward pointing
+//! one, all y coordinates are negated so that the drawing keeps its
+//! orientation.
+
+use std::io::{self, Write};
+
+use {Color, Polyline, Shape};
+use util::is_closed;
+
+/// Name of the layer used for polylines without layer information.
+const DEFAULT_LAYER: &str = "0";
+
+/// The AutoCAD color index (ACI) of black / white, depending on background.
+const ACI_FOREGROUND: u8 = 7;
+
+/// RGB values of the standard AutoCAD color indices 1 to 9.
+const ACI_COLORS: &[(u8, (u8, u8, u8))] = &[
+    (1, (255, 0, 0)),
+    (2, (255, 255, 0)),
+    (3, (0, 255, 0)),
+    (4, (0, 255, 255)),
+    (5, (0, 0, 255)),
+    (6, (255, 0, 255)),
+    (7, (255, 255, 255)),
+    (8, (128, 128, 128)),
+    (9, (192, 192, 192)),
+];
+
+/// Map an RGB color to the nearest standard AutoCAD color index.
+///
+/// Black and white both map to index 7, which CAD programs render in the
+/// foreground color of the drawing area.
+fn color_index(color: Color) -> u8 {
+    if color == Color::new(0, 0, 0) {
+        return ACI_FOREGROUND;
+    }
+    let distance = |&(_, (r, g, b)): &(u8, (u8, u8, u8))| {
+        let dr = i32::from(color.r) - i32::from(r);
+        let dg = i32::from(color.g) - i32::from(g);
+        let db = i32::from(color.b) - i32::from(b);
+        dr * dr + dg * dg + db * db
+    };
+    ACI_COLORS.iter()
+        .min_by_key(|entry| distance(entry))
+        .map(|&(index, _)| index)
+        .unwrap_or(ACI_FOREGROUND)
+}
+
+/// Convert an arbitrary layer name into a name that is valid in all DXF
+/// versions (letters, digits, `$`, `-` and `_` only).
+fn layer_name(name: &str) -> String {
+    let name: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '$' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() { DEFAULT_LAYER.into() } else { name.to_uppercase() }
+}
+
+/// A DXF layer with its AutoCAD color index.
+struct Layer {
+    name: String,
+    color: u8,
+}
+
+/// The `$INSUNITS` value for millimeters.
+const UNITS_MILLIMETERS: u8 = 4;
+
+/// The `$MEASUREMENT` value for metric drawings.
+const MEASUREMENT_METRIC: u8 = 1;
+
+/// Low level writer for DXF group code / value pairs.
+struct DxfWriter<'a, W: Write + 'a> {
+    out: &'a mut W,
+    /// The last handle assigned to a table or entity.
+    handle: usize,
+}
+
+impl<'a, W: Write> DxfWriter<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        DxfWriter { out, handle: 0 }
+    }
+
+    fn pair<T: ::std::fmt::Display>(&mut self, code: u16, value: T) -> io::Result<()> {
+        write!(self.out, "{:>3}\n{}\n", code, value)
+    }
+
+    /// Write the next free handle.
+    fn handle(&mut self) -> io::Result<()> {
+        self.handle += 1;
+        let handle = format!("{:X}", self.handle);
+        self.pair(5, handle)
+    }
+
+    /// Write the header, where `handles` is the number of handles used in
+    /// the rest of the file.
+    fn header(&mut self, handles: usize) -> io::Result<()> {
+        self.pair(0, "SECTION")?;
+        self.pair(2, "HEADER")?;
+        self.pair(9, "$ACADVER")?;
+        self.pair(1, "AC1015")?;
+        self.pair(9, "$HANDSEED")?;
+        self.pair(5, format!("{:X}", handles + 1))?;
+        self.pair(9, "$INSUNITS")?;
+        self.pair(70, UNITS_MILLIMETERS)?;
+        self.pair(9, "$MEASUREMENT")?;
+        self.pair(70, MEASUREMENT_METRIC)?;
+        self.pair(0, "ENDSEC")
+    }
+
+    fn tables(&mut self, layers: &[Layer]) -> io::Result<()> {
+        self.pair(0, "SECTION")?;
+        self.pair(2, "TABLES")?;
+
+        self.pair(0, "TABLE")?;
+        self.pair(2, "LTYPE")?;
+        self.handle()?;
+        self.pair(100, "AcDbSymbolTable")?;
+        self.pair(70, 1)?;
+        self.pair(0, "LTYPE")?;
+        self.handle()?;
+        self.pair(100, "AcDbSymbolTableRecord")?;
+        self.pair(100, "AcDbLinetypeTableRecord")?;
+        self.pair(2, "CONTINUOUS")?;
+        self.pair(70, 0)?;
+        self.pair(3, "Solid line")?;
+        self.pair(72, 65)?;
+        self.pair(73, 0)?;
+        self.pair(40, 0.0)?;
+        self.pair(0, "ENDTAB")?;
+
+        self.pair(0, "TABLE")?;
+        self.pair(2, "LAYER")?;
+        self.handle()?;
+        self.pair(100, "AcDbSymbolTable")?;
+        self.pair(70, layers.len())?;
+        for layer in layers {
+            self.pair(0, "LAYER")?;
+            self.handle()?;
+            self.pair(100, "AcDbSymbolTableRecord")?;
+            self.pair(100, "AcDbLayerTableRecord")?;
+            self.pair(2, &layer.name)?;
+            self.pair(70, 0)?;
+            self.pair(62, layer.color)?;
+            self.pair(6, "CONTINUOUS")?;
+        }
+        self.pair(0, "ENDTAB")?;
+
+        self.pair(0, "ENDSEC")
+    }
+
+    /// Write a polyline entity. Without a color, the entity uses the color of
+    /// its layer (`BYLAYER`).
+    fn polyline(&mut self, layer: &str, color: Option<u8>, polyline: &Polyline) -> io::Result<()> {
+        let closed = is_closed(polyline);
+        let vertices = if closed { &polyline[..polyline.len() - 1] } else { &polyline[..] };
+
+        self.pair(0, "LWPOLYLINE")?;
+        self.handle()?;
+        self.pair(100, "AcDbEntity")?;
+        self.pair(8, layer)?;
+        if let Some(color) = color {
+            self.pair(62, color)?;
+        }
+        self.pair(100, "AcDbPolyline")?;
+        self.pair(90, vertices.len())?;
+        self.pair(70, if closed { 1 } else { 0 })?;
+        for vertex in vertices {
+            // Adding zero turns the negated zero into a positive one
+            self.pair(10, vertex.x + 0.0)?;
+            self.pair(20, -vertex.y + 0.0)?;
+        }
+        Ok(())
+    }
+
+    fn document<'b, I>(&mut self, layers: &[Layer], entities: I) -> io::Result<()>
+        where I: Iterator<Item=(&'b str, Option<u8>, &'b Polyline)>
+    {
+        let entities: Vec<_> = entities.filter(|&(_, _, polyline)| polyline.len() > 1).collect();
+        // One handle for every table, line type, layer and entity
+        self.header(3 + layers.len() + entities.len())?;
+        self.tables(layers)?;
+        self.pair(0, "SECTION")?;
+        self.pair(2, "ENTITIES")?;
+        for (layer, color, polyline) in entities {
+            self.polyline(layer, color, polyline)?;
+        }
+        self.pair(0, "ENDSEC")?;
+        self.pair(0, "EOF")
+    }
+}
+
+/// Write the polylines as a DXF document to `out`, all on layer `0`.
+pub fn write_polylines<W: Write>(out: &mut W, polylines: &[Polyline]) -> io::Result<()> {
+    let layers = [Layer { name: DEFAULT_LAYER.into(), color: ACI_FOREGROUND }];
+    DxfWriter::new(out).document(
+        &layers,
+        polylines.iter().map(|polyline| (DEFAULT_LAYER, None, polyline)),
+    )
+}
+
+/// Write the shapes as a DXF document to `out`.
+///
+/// Every SVG layer becomes a DXF layer. Stroke colors are mapped to the
+/// nearest AutoCAD color index. If all shapes on a layer share the same
+/// color, it becomes the color of the layer, otherwise every entity on the
+/// layer gets its own color.
+pub fn write_shapes<W: Write>(out: &mut W, shapes: &[Shape]) -> io::Result<()> {
+    let mut layers: Vec<Layer> = Vec::new();
+    // Whether all shapes on the layer with the same index share its color
+    let mut uniform: Vec<bool> = Vec::new();
+    let mut shape_layers = Vec::with_capacity(shapes.len());
+    for shape in shapes {
+        let name = shape.layer.as_ref().map_or_else(|| DEFAULT_LAYER.into(), |l| layer_name(l));
+        let color = shape.stroke.map_or(ACI_FOREGROUND, color_index);
+        let index = match layers.iter().position(|layer| layer.name == name) {
+            Some(i) => {
+                uniform[i] &= layers[i].color == color;
+                i
+            },
+            None => {
+                layers.push(Layer { name, color });
+                uniform.push(true);
+                layers.len() - 1
+            },
+        };
+        shape_layers.push((index, color));
+    }
+    for (layer, _) in layers.iter_mut().zip(&uniform).filter(|&(_, &uniform)| !uniform) {
+        layer.color = ACI_FOREGROUND;
+    }
+
+    let entities = shapes.iter()
+        .zip(shape_layers)
+        .flat_map(|(shape, (index, color))| {
+            let name = layers[index].name.as_str();
+            let color = if uniform[index] { None } else { Some(color) };
+            shape.polylines.iter().map(move |p| (name, color, p))
+        });
+    DxfWriter::new(out).document(&layers, entities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(result: Vec<u8>) -> String {
+        String::from_utf8(result).unwrap()
+    }
+
+    #[test]
+    fn test_color_index() {
+        assert_eq!(color_index(Color::new(0, 0, 0)), 7);
+        assert_eq!(color_index(Color::new(250, 10, 10)), 1);
+        assert_eq!(color_index(Color::new(0, 0, 200)), 5);
+        assert_eq!(color_index(Color::new(120, 120, 130)), 8);
+    }
+
+    #[test]
+    fn test_layer_name() {
+        assert_eq!(layer_name("layer 1"), "LAYER_1");
+        assert_eq!(layer_name("cut-outer"), "CUT-OUTER");
+        assert_eq!(layer_name(""), "0");
+    }
+
+    #[test]
+    fn test_write_polylines() {
+        let polylines = vec![
+            vec![(0.0, 0.0).into(), (10.0, 5.0).into()],
+            vec![(0.0, 0.0).into(), (10.0, 0.0).into(), (10.0, 10.0).into(), (0.0, 0.0).into()],
+        ];
+        let mut out = Vec::new();
+        write_polylines(&mut out, &polylines).unwrap();
+        let dxf = to_string(out);
+
+        assert!(dxf.starts_with("  0\nSECTION\n  2\nHEADER\n"));
+        assert!(dxf.contains("$ACADVER\n  1\nAC1015\n"));
+        assert!(dxf.contains("$INSUNITS\n 70\n4\n"));
+        assert!(dxf.contains("$MEASUREMENT\n 70\n1\n"));
+        // Two tables, a line type, a layer and two entities use handles
+        assert!(dxf.contains("$HANDSEED\n  5\n7\n"));
+        assert!(dxf.ends_with("  0\nEOF\n"));
+        assert_eq!(dxf.matches("LWPOLYLINE\n").count(), 2);
+        assert!(dxf.contains(" 90\n2\n 70\n0\n 10\n0\n 20\n0\n 10\n10\n 20\n-5\n"));
+        // The closed polyline omits the duplicated end point
+        assert!(dxf.contains(" 90\n3\n 70\n1\n 10\n0\n 20\n0\n 10\n10\n 20\n0\n 10\n10\n 20\n-10\n  0\n"));
+    }
+
+    #[test]
+    fn test_write_shapes_layers() {
+        let shapes = vec![
+            Shape {
+                layer: Some("outline".into()),
+                stroke: Some(Color::new(255, 0, 0)),
+                polylines: vec![vec![(0.0, 0.0).into(), (1.0, 1.0).into()]],
+                ..Shape::default()
+            },
+            Shape {
+                layer: None,
+                stroke: None,
+                polylines: vec![vec![(2.0, 2.0).into(), (3.0, 3.0).into()]],
+                ..Shape::default()
+            },
+            Shape {
+                layer: Some("outline".into()),
+                stroke: Some(Color::new(0, 0, 255)),
+                polylines: vec![vec![(4.0, 4.0).into(), (5.0, 5.0).into()]],
+                ..Shape::default()
+            },
+        ];
+        let mut out = Vec::new();
+        write_shapes(&mut out, &shapes).unwrap();
+        let dxf = to_string(out);
+
+        assert!(dxf.contains("AcDbSymbolTable\n 70\n2\n"));
+        // The shapes on OUTLINE have different colors, so every polyline
+        // keeps its own
+        assert!(dxf.contains("AcDbLayerTableRecord\n  2\nOUTLINE\n 70\n0\n 62\n7\n"));
+        assert!(dxf.contains("AcDbEntity\n  8\nOUTLINE\n 62\n1\n"));
+        assert!(dxf.contains("AcDbEntity\n  8\nOUTLINE\n 62\n5\n"));
+        // A layer with a single color uses it for all of its polylines
+        assert!(dxf.contains("AcDbLayerTableRecord\n  2\n0\n 70\n0\n 62\n7\n"));
+        assert!(dxf.contains("AcDbEntity\n  8\n0\n100\nAcDbPolyline\n"));
+    }
+}
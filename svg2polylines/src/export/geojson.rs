@@ -0,0 +1,120 @@
+//! Export polylines as GeoJSON (RFC 7946).
+//!
+//! Polylines are written as `MultiLineString` geometries. Shapes are written
+//! as a `FeatureCollection` with one feature per shape, carrying the layer
+//! and the stroke color (using the `stroke` property of the widely supported
+//! simplestyle specification) as properties.
+//!
+//! Coordinates are written unchanged, so they are not geographic
+//! coordinates and the y axis points downwards, like in SVG. GeoJSON
+//! positions must be numbers, so lines are split at points with NaN or
+//! infinite coordinates, which are left out.
+
+use std::io::{self, Write};
+
+use {CoordinatePair, Polyline, Shape};
+use super::json::json_coordinates;
+use super::json_string;
+
+fn is_finite(p: &CoordinatePair) -> bool {
+    p.x.is_finite() && p.y.is_finite()
+}
+
+fn multi_line_string(polylines: &[Polyline], precision: usize) -> String {
+    let lines: Vec<String> = polylines.iter()
+        .flat_map(|p| p.split(|q| !is_finite(q)))
+        .filter(|p| p.len() > 1)
+        .map(|p| json_coordinates(p, precision))
+        .collect();
+    format!(r#"{{"type":"MultiLineString","coordinates":[{}]}}"#, lines.join(","))
+}
+
+/// Write the polylines as a GeoJSON `MultiLineString` geometry to `out`, with
+/// `precision` decimal places.
+pub fn write_multi_line_string<W: Write>(out: &mut W, polylines: &[Polyline], precision: usize) -> io::Result<()> {
+    write!(out, "{}", multi_line_string(polylines, precision))
+}
+
+/// Write the shapes as a GeoJSON `FeatureCollection` to `out`, with
+/// `precision` decimal places.
+pub fn write_feature_collection<W: Write>(out: &mut W, shapes: &[Shape], precision: usize) -> io::Result<()> {
+    write!(out, r#"{{"type":"FeatureCollection","features":["#)?;
+    for (i, shape) in shapes.iter().enumerate() {
+        let mut properties = Vec::new();
+        if let Some(ref layer) = shape.layer {
+            properties.push(format!(r#""layer":{}"#, json_string(layer)));
+        }
+        if let Some(stroke) = shape.stroke {
+            properties.push(format!(r#""stroke":{}"#, json_string(&stroke.to_hex())));
+        }
+        write!(
+            out,
+            r#"{}{{"type":"Feature","geometry":{},"properties":{{{}}}}}"#,
+            if i > 0 { "," } else { "" },
+            multi_line_string(&shape.polylines, precision),
+            properties.join(","),
+        )?;
+    }
+    write!(out, "]}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Color;
+
+    #[test]
+    fn test_write_multi_line_string() {
+        let polylines = vec![
+            vec![(0.0, 0.0).into(), (1.5, 2.0).into()],
+            vec![(3.0, 3.0).into()],
+        ];
+        let mut out = Vec::new();
+        write_multi_line_string(&mut out, &polylines, 3).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"type":"MultiLineString","coordinates":[[[0,0],[1.5,2]]]}"#,
+        );
+    }
+
+    #[test]
+    fn test_non_finite_coordinates() {
+        let polylines = vec![vec![
+            (0.0, 0.0).into(),
+            (1.0, 1.0).into(),
+            (f64::NAN, 2.0).into(),
+            (3.0, 3.0).into(),
+            (4.0, f64::INFINITY).into(),
+            (5.0, 5.0).into(),
+            (6.0, 6.0).into(),
+        ]];
+        let mut out = Vec::new();
+        write_multi_line_string(&mut out, &polylines, 3).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"type":"MultiLineString","coordinates":[[[0,0],[1,1]],[[5,5],[6,6]]]}"#,
+        );
+    }
+
+    #[test]
+    fn test_write_feature_collection() {
+        let shapes = vec![
+            Shape {
+                layer: Some("roads".into()),
+                stroke: Some(Color::new(0, 0, 255)),
+                polylines: vec![vec![(0.0, 0.0).into(), (1.0, 1.0).into()]],
+                ..Shape::default()
+            },
+            Shape::default(),
+        ];
+        let mut out = Vec::new();
+        write_feature_collection(&mut out, &shapes, 3).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"type":"FeatureCollection","features":["#,
+            r#"{"type":"Feature","geometry":{"type":"MultiLineString","coordinates":[[[0,0],[1,1]]]},"#,
+            r##""properties":{"layer":"roads","stroke":"#0000ff"}},"##,
+            r#"{"type":"Feature","geometry":{"type":"MultiLineString","coordinates":[]},"properties":{}}"#,
+            r#"]}"#,
+        ));
+    }
+}
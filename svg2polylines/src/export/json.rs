@@ -0,0 +1,131 @@
+//! Export polylines as compact JSON.
+//!
+//! The document contains some metadata about the result as well as the
+//! polylines, grouped by shape when writing shapes:
+//!
+//! ```json
+//! {
+//!   "generator": "svg2polylines 0.4.0",
+//!   "metadata": {"shapes": 1, "polylines": 1, "points": 2, "bounds": [0, 0, 10, 5]},
+//!   "shapes": [
+//!     {"layer": "layer1", "stroke": "#ff0000", "polylines": [[[0, 0], [10, 5]]]}
+//!   ]
+//! }
+//! ```
+//!
+//! `bounds` is `[min_x, min_y, max_x, max_y]`, or `null` if there are no
+//! points. `layer` and `stroke` are `null` if not set. When writing plain
+//! polylines, there is a single shape without layer and stroke.
+//!
+//! The output is written without any whitespace. Coordinates that are NaN or
+//! infinite are written as `null`.
+
+use std::io::{self, Write};
+
+use {CoordinatePair, Polyline, Shape};
+use super::{json_number, json_string};
+use util::bounds;
+
+/// Format a polyline as JSON array of `[x, y]` arrays.
+pub(crate) fn json_coordinates(polyline: &[CoordinatePair], precision: usize) -> String {
+    let points: Vec<String> = polyline.iter()
+        .map(|p| format!("[{},{}]", json_number(p.x, precision), json_number(p.y, precision)))
+        .collect();
+    format!("[{}]", points.join(","))
+}
+
+fn optional_string(value: Option<String>) -> String {
+    value.map_or_else(|| "null".into(), |v| json_string(&v))
+}
+
+/// Write the shapes as JSON document to `out`, with `precision` decimal
+/// places.
+pub fn write_shapes<W: Write>(out: &mut W, shapes: &[Shape], precision: usize) -> io::Result<()> {
+    let polylines = shapes.iter().flat_map(|s| s.polylines.iter());
+    let points: usize = polylines.clone().map(|p| p.len()).sum();
+    let bounds = match bounds(polylines.clone().flat_map(|p| p.iter())) {
+        Some((min, max)) => {
+            let values: Vec<String> = [min.x, min.y, max.x, max.y].iter()
+                .map(|&v| json_number(v, precision))
+                .collect();
+            format!("[{}]", values.join(","))
+        },
+        None => "null".into(),
+    };
+
+    write!(
+        out,
+        r#"{{"generator":{},"metadata":{{"shapes":{},"polylines":{},"points":{},"bounds":{}}},"shapes":["#,
+        json_string(concat!("svg2polylines ", env!("CARGO_PKG_VERSION"))),
+        shapes.len(),
+        polylines.count(),
+        points,
+        bounds,
+    )?;
+    for (i, shape) in shapes.iter().enumerate() {
+        let lines: Vec<String> = shape.polylines.iter()
+            .map(|p| json_coordinates(p, precision))
+            .collect();
+        write!(
+            out,
+            r#"{}{{"layer":{},"stroke":{},"polylines":[{}]}}"#,
+            if i > 0 { "," } else { "" },
+            optional_string(shape.layer.clone()),
+            optional_string(shape.stroke.map(|c| c.to_hex())),
+            lines.join(","),
+        )?;
+    }
+    write!(out, "]}}")
+}
+
+/// Write the polylines as JSON document to `out`, with `precision` decimal
+/// places.
+pub fn write_polylines<W: Write>(out: &mut W, polylines: &[Polyline], precision: usize) -> io::Result<()> {
+    let shape = Shape {
+        polylines: polylines.to_vec(),
+        ..Shape::default()
+    };
+    write_shapes(out, &[shape], precision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Color;
+
+    #[test]
+    fn test_write_shapes() {
+        let shapes = vec![
+            Shape {
+                layer: Some("layer1".into()),
+                stroke: Some(Color::new(255, 0, 0)),
+                polylines: vec![vec![(0.0, 0.0).into(), (10.0, 5.0).into()]],
+                ..Shape::default()
+            },
+            Shape {
+                layer: None,
+                stroke: None,
+                polylines: vec![vec![(1.0 / 3.0, -2.0).into(), (4.0, 4.0).into(), (5.0, 5.0).into()]],
+                ..Shape::default()
+            },
+        ];
+        let mut out = Vec::new();
+        write_shapes(&mut out, &shapes, 2).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert_eq!(json, concat!(
+            r#"{"generator":"svg2polylines "#, env!("CARGO_PKG_VERSION"), r#"","#,
+            r#""metadata":{"shapes":2,"polylines":2,"points":5,"bounds":[0,-2,10,5]},"#,
+            r##""shapes":[{"layer":"layer1","stroke":"#ff0000","polylines":[[[0,0],[10,5]]]},"##,
+            r#"{"layer":null,"stroke":null,"polylines":[[[0.33,-2],[4,4],[5,5]]]}]}"#,
+        ));
+    }
+
+    #[test]
+    fn test_write_polylines_empty() {
+        let mut out = Vec::new();
+        write_polylines(&mut out, &[], 2).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains(r#""metadata":{"shapes":1,"polylines":0,"points":0,"bounds":null}"#));
+        assert!(json.ends_with(r#""shapes":[{"layer":null,"stroke":null,"polylines":[]}]}"#));
+    }
+}
@@ -0,0 +1,75 @@
+//! Writers that serialize polylines into other file formats.
+
+pub mod dxf;
+pub mod svg;
+pub mod diagnostic;
+pub mod raster;
+pub mod geojson;
+pub mod csv;
+pub mod json;
+
+/// Format a number with at most `precision` decimal places, without trailing
+/// zeros.
+pub(crate) fn format_number(value: f64, precision: usize) -> String {
+    let mut s = format!("{:.*}", precision, value);
+    if s.contains('.') {
+        let trimmed = s.trim_end_matches('0').trim_end_matches('.').len();
+        s.truncate(trimmed);
+    }
+    if s == "-0" {
+        s = "0".into();
+    }
+    s
+}
+
+/// Format a number for use in JSON, see `format_number`. JSON has no
+/// representation of NaN and infinity, so they are written as `null`.
+pub(crate) fn json_number(value: f64, precision: usize) -> String {
+    if value.is_finite() { format_number(value, precision) } else { "null".into() }
+}
+
+/// Quote and escape a string for use in JSON.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(1.0, 3), "1");
+        assert_eq!(format_number(1.23456, 3), "1.235");
+        assert_eq!(format_number(-0.0001, 3), "0");
+        assert_eq!(format_number(10.5, 0), "10");
+        assert_eq!(format_number(100.0, 2), "100");
+    }
+
+    #[test]
+    fn test_json_number() {
+        assert_eq!(json_number(1.5, 3), "1.5");
+        assert_eq!(json_number(f64::NAN, 3), "null");
+        assert_eq!(json_number(f64::NEG_INFINITY, 3), "null");
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("layer 1"), r#""layer 1""#);
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+    }
+}
@@ -0,0 +1,315 @@
+//! Render polylines into an RGBA pixel buffer and encode it as PNG.
+//!
+//! This is a small CPU-only rasterizer meant for previews and visual
+//! regression tests. It does not need a graphics context or window, so it
+//! also works on headless machines. Lines are drawn anti-aliased with round
+//! caps and joins.
+//!
+//! The PNG encoder uses uncompressed deflate blocks, so the files are larger
+//! than they would be with a full PNG library, but valid and exact.
+
+use std::io::{self, Write};
+
+use {Color, CoordinatePair, Polyline};
+use util::{bounds, segment_distance, Segment};
+
+/// Options for the rasterizer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RasterOptions {
+    /// Pixels per user unit.
+    pub scale: f64,
+    /// The pen width in user units.
+    pub pen_width: f64,
+    /// The area to render `(width, height)` in user units, starting at the
+    /// origin. If `None`, the bounding box of the polylines is rendered.
+    pub size: Option<(f64, f64)>,
+    /// Empty space around the rendered area in user units.
+    pub padding: f64,
+    /// The pen color.
+    pub foreground: Color,
+    /// The background color.
+    pub background: Color,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        RasterOptions {
+            scale: 4.0,
+            pen_width: 0.5,
+            size: None,
+            padding: 2.0,
+            foreground: Color::new(0, 0, 0),
+            background: Color::new(255, 255, 255),
+        }
+    }
+}
+
+/// An RGBA image with 8 bits per channel.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Raster {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Create an image filled with a single color.
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&[background.r, background.g, background.b, 255]);
+        }
+        Raster { width, height, pixels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The raw RGBA data, row by row, starting at the top left corner.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Return the RGBA value of the pixel at `(x, y)`.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// Return the largest difference of any channel of any pixel between the
+    /// two images, or `None` if they do not have the same dimensions.
+    ///
+    /// This is useful for visual regression tests that should tolerate small
+    /// differences in anti-aliasing.
+    pub fn max_difference(&self, other: &Raster) -> Option<u8> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+        Some(self.pixels.iter()
+            .zip(other.pixels.iter())
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Encode the image as PNG and write it to `out`.
+    ///
+    /// Returns an error for images without pixels, which PNG cannot
+    /// represent.
+    pub fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cannot write a PNG image of {} × {} pixels", self.width, self.height),
+            ));
+        }
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth 8, color type RGBA, default compression, filter and interlacing
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_chunk(out, b"IHDR", &header)?;
+
+        // Every scanline is prefixed with filter type 0 (none)
+        let mut scanlines = Vec::with_capacity((self.width * 4 + 1) * self.height);
+        for row in self.pixels.chunks(self.width * 4) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_chunk(out, b"IDAT", &zlib_stored(&scanlines))?;
+
+        write_chunk(out, b"IEND", &[])
+    }
+}
+
+/// Write a PNG chunk including length and checksum.
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data.iter()));
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// CRC-32 as used by PNG (ISO 3309).
+fn crc32<'a, I: Iterator<Item=&'a u8>>(data: I) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    !data.fold(!0u32, |crc, &byte| table[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// Wrap the data in a zlib stream using uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65_535;
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(if last { 1 } else { 0 });
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+/// Render the polylines into an image.
+pub fn render(polylines: &[Polyline], options: &RasterOptions) -> Raster {
+    let (origin, (width, height)) = match options.size {
+        Some(size) => (CoordinatePair::new(0.0, 0.0), size),
+        None => match bounds(polylines.iter().flat_map(|p| p.iter())) {
+            Some((min, max)) => (min, (max.x - min.x, max.y - min.y)),
+            None => (CoordinatePair::new(0.0, 0.0), (0.0, 0.0)),
+        },
+    };
+    let scale = options.scale;
+    let to_pixels = |v: f64| ((v + 2.0 * options.padding) * scale).ceil().max(1.0) as usize;
+    let (pixel_width, pixel_height) = (to_pixels(width), to_pixels(height));
+    let transform = |p: &CoordinatePair| CoordinatePair::new(
+        (p.x - origin.x + options.padding) * scale,
+        (p.y - origin.y + options.padding) * scale,
+    );
+
+    // Pen radius in pixels. Pens thinner than a pixel are drawn one pixel
+    // wide with reduced intensity.
+    let radius = (options.pen_width * scale / 2.0).max(0.5);
+    let intensity = (options.pen_width * scale).min(1.0);
+
+    let mut coverage = vec![0f64; pixel_width * pixel_height];
+    for polyline in polylines {
+        let points: Vec<CoordinatePair> = polyline.iter().map(&transform).collect();
+        let segments: Vec<Segment> = if points.len() == 1 {
+            vec![(points[0], points[0])]
+        } else {
+            points.windows(2).map(|w| (w[0], w[1])).collect()
+        };
+        for (a, b) in segments {
+            let reach = radius + 1.0;
+            let x0 = (a.x.min(b.x) - reach).floor().max(0.0) as usize;
+            let y0 = (a.y.min(b.y) - reach).floor().max(0.0) as usize;
+            let x1 = ((a.x.max(b.x) + reach).ceil().max(0.0) as usize).min(pixel_width);
+            let y1 = ((a.y.max(b.y) + reach).ceil().max(0.0) as usize).min(pixel_height);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let d = segment_distance(&CoordinatePair::new(x as f64 + 0.5, y as f64 + 0.5), &a, &b);
+                    let c = (radius + 0.5 - d).clamp(0.0, 1.0) * intensity;
+                    let cell = &mut coverage[y * pixel_width + x];
+                    if c > *cell {
+                        *cell = c;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut raster = Raster::new(pixel_width, pixel_height, options.background);
+    let (fg, bg) = (options.foreground, options.background);
+    let blend = |f: u8, b: u8, c: f64| (f64::from(f) * c + f64::from(b) * (1.0 - c)).round() as u8;
+    for (i, &c) in coverage.iter().enumerate() {
+        if c > 0.0 {
+            raster.pixels[i * 4] = blend(fg.r, bg.r, c);
+            raster.pixels[i * 4 + 1] = blend(fg.g, bg.g, c);
+            raster.pixels[i * 4 + 2] = blend(fg.b, bg.b, c);
+        }
+    }
+    raster
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+        assert_eq!(crc32(b"123456789".iter()), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_zlib_stored() {
+        let data = zlib_stored(b"abc");
+        assert_eq!(data, vec![0x78, 0x01, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27]);
+        let large = zlib_stored(&vec![0; 70_000]);
+        assert_eq!(large.len(), 2 + 5 + 65_535 + 5 + 4_465 + 4);
+    }
+
+    #[test]
+    fn test_render() {
+        let polylines = vec![
+            vec![(0.0, 2.5).into(), (10.0, 2.5).into()],
+            vec![(0.0, 7.0).into(), (10.0, 7.0).into()],
+        ];
+        let options = RasterOptions {
+            scale: 1.0,
+            pen_width: 1.0,
+            size: Some((10.0, 10.0)),
+            padding: 0.0,
+            ..RasterOptions::default()
+        };
+        let raster = render(&polylines, &options);
+        assert_eq!((raster.width(), raster.height()), (10, 10));
+        assert_eq!(raster.pixels().len(), 10 * 10 * 4);
+
+        // A line through pixel centers covers a single row
+        assert_eq!(raster.pixel(5, 1), [255, 255, 255, 255]);
+        assert_eq!(raster.pixel(5, 2), [0, 0, 0, 255]);
+        assert_eq!(raster.pixel(5, 3), [255, 255, 255, 255]);
+
+        // A line between pixel centers covers two rows by half
+        assert_eq!(raster.pixel(5, 6), [128, 128, 128, 255]);
+        assert_eq!(raster.pixel(5, 7), [128, 128, 128, 255]);
+
+        let empty = Raster::new(10, 10, Color::new(255, 255, 255));
+        assert_eq!(raster.max_difference(&raster), Some(0));
+        assert_eq!(raster.max_difference(&empty), Some(255));
+        assert_eq!(raster.max_difference(&Raster::new(1, 1, Color::new(0, 0, 0))), None);
+    }
+
+    #[test]
+    fn test_write_png() {
+        let raster = Raster::new(2, 1, Color::new(255, 0, 0));
+        let mut out = Vec::new();
+        raster.write_png(&mut out).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&out[8..16], b"\x00\x00\x00\x0dIHDR");
+        assert_eq!(&out[16..29], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        assert_eq!(&out[out.len() - 12..], b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+    }
+
+    #[test]
+    fn test_empty() {
+        // An empty drawing without padding still has a pixel
+        let options = RasterOptions { padding: 0.0, ..RasterOptions::default() };
+        let raster = render(&[], &options);
+        assert_eq!((raster.width(), raster.height()), (1, 1));
+        assert!(raster.write_png(&mut Vec::new()).is_ok());
+
+        let raster = Raster::new(0, 3, Color::new(255, 255, 255));
+        let error = raster.write_png(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
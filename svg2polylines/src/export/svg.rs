@@ -0,0 +1,265 @@
+//! Export polylines as a plotter friendly SVG document.
+//!
+//! The generated document only contains `<polyline>` or `<path>` elements
+//! (optionally grouped per layer), so it shows exactly what a plotter will
+//! draw. Coordinates are written unchanged in user units; the document size
+//! determines which physical unit a user unit corresponds to.
+
+use std::io::{self, Write};
+
+use {Color, CoordinatePair, Polyline, Shape};
+use super::format_number;
+use util::{bounds, is_closed};
+
+/// The element type used for every polyline.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Element {
+    /// `<polyline points="...">`
+    Polyline,
+    /// `<path d="...">`, closed polylines are terminated with `Z`.
+    Path,
+}
+
+/// Options for the SVG writer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SvgOptions {
+    /// The number of decimal places written for coordinates.
+    pub precision: usize,
+    /// The document size `(width, height)` in `unit`. If `None`, the
+    /// bounding box of the polylines is used as document.
+    pub size: Option<(f64, f64)>,
+    /// The physical unit of the document, e.g. `mm`, `in` or `px`.
+    pub unit: String,
+    /// The element type used for the polylines.
+    pub element: Element,
+    /// The stroke width in user units.
+    pub stroke_width: f64,
+    /// The stroke color used for polylines without a color of their own.
+    pub stroke: Color,
+    /// Whether to write one `<g>` element per layer when writing shapes.
+    pub group_layers: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            precision: 3,
+            size: None,
+            unit: "mm".into(),
+            element: Element::Polyline,
+            stroke_width: 0.3,
+            stroke: Color::new(0, 0, 0),
+            group_layers: true,
+        }
+    }
+}
+
+/// Escape a string for use in an XML attribute value.
+pub(crate) fn escape_xml(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Low level writer for SVG elements.
+pub(crate) struct SvgWriter<'a, W: Write + 'a> {
+    pub(crate) out: &'a mut W,
+    pub(crate) options: &'a SvgOptions,
+}
+
+impl<'a, W: Write> SvgWriter<'a, W> {
+    pub(crate) fn number(&self, value: f64) -> String {
+        format_number(value, self.options.precision)
+    }
+
+    pub(crate) fn point(&self, p: &CoordinatePair) -> String {
+        format!("{},{}", self.number(p.x), self.number(p.y))
+    }
+
+    /// Write the XML declaration and the opening `<svg>` tag.
+    ///
+    /// The view box is derived from the document size, or from the bounding
+    /// box of `points` if no size was configured.
+    pub(crate) fn start<'b, I>(&mut self, points: I) -> io::Result<()>
+        where I: IntoIterator<Item=&'b CoordinatePair>
+    {
+        let (origin, (width, height)) = match self.options.size {
+            Some(size) => (CoordinatePair::new(0.0, 0.0), size),
+            None => match bounds(points) {
+                Some((min, max)) => (min, (max.x - min.x, max.y - min.y)),
+                None => (CoordinatePair::new(0.0, 0.0), (0.0, 0.0)),
+            },
+        };
+        let unit = escape_xml(&self.options.unit);
+        writeln!(self.out, r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#)?;
+        writeln!(
+            self.out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{w}{u}" height="{h}{u}" viewBox="{x} {y} {w} {h}">"#,
+            w = self.number(width),
+            h = self.number(height),
+            u = unit,
+            x = self.number(origin.x),
+            y = self.number(origin.y),
+        )
+    }
+
+    pub(crate) fn end(&mut self) -> io::Result<()> {
+        writeln!(self.out, "</svg>")
+    }
+
+    /// Open a group with the given stroke color and optional id.
+    pub(crate) fn start_group(&mut self, id: Option<&str>, stroke: Color) -> io::Result<()> {
+        let id = id.map(|id| format!(r#" id="{}""#, escape_xml(id))).unwrap_or_default();
+        writeln!(
+            self.out,
+            r#"<g{} fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round">"#,
+            id, stroke.to_hex(), self.number(self.options.stroke_width),
+        )
+    }
+
+    pub(crate) fn end_group(&mut self) -> io::Result<()> {
+        writeln!(self.out, "</g>")
+    }
+
+    pub(crate) fn polyline(&mut self, polyline: &[CoordinatePair], attributes: &str) -> io::Result<()> {
+        if polyline.len() < 2 {
+            return Ok(());
+        }
+        match self.options.element {
+            Element::Polyline => {
+                let points: Vec<String> = polyline.iter().map(|p| self.point(p)).collect();
+                writeln!(self.out, r#"<polyline points="{}"{}/>"#, points.join(" "), attributes)
+            },
+            Element::Path => {
+                let closed = is_closed(polyline);
+                let vertices = if closed { &polyline[..polyline.len() - 1] } else { polyline };
+                let mut d = format!("M{}", self.point(&vertices[0]));
+                for p in &vertices[1..] {
+                    d.push_str(" L");
+                    d.push_str(&self.point(p));
+                }
+                if closed {
+                    d.push_str(" Z");
+                }
+                writeln!(self.out, r#"<path d="{}"{}/>"#, d, attributes)
+            },
+        }
+    }
+}
+
+/// Write the polylines as an SVG document to `out`.
+pub fn write_polylines<W: Write>(out: &mut W, polylines: &[Polyline], options: &SvgOptions) -> io::Result<()> {
+    let mut writer = SvgWriter { out, options };
+    writer.start(polylines.iter().flat_map(|p| p.iter()))?;
+    writer.start_group(None, options.stroke)?;
+    for polyline in polylines {
+        writer.polyline(polyline, "")?;
+    }
+    writer.end_group()?;
+    writer.end()
+}
+
+/// Write the shapes as an SVG document to `out`.
+///
+/// If `group_layers` is enabled, shapes are grouped into one `<g>` element
+/// per layer (in order of first appearance), so that every group corresponds
+/// to one pen. Shapes keep their stroke color.
+pub fn write_shapes<W: Write>(out: &mut W, shapes: &[Shape], options: &SvgOptions) -> io::Result<()> {
+    let mut writer = SvgWriter { out, options };
+    writer.start(shapes.iter().flat_map(|s| s.polylines.iter()).flat_map(|p| p.iter()))?;
+
+    let mut layers: Vec<Option<&str>> = Vec::new();
+    if options.group_layers {
+        for shape in shapes {
+            let layer = shape.layer.as_deref();
+            if !layers.contains(&layer) {
+                layers.push(layer);
+            }
+        }
+    } else {
+        layers.push(None);
+    }
+
+    for layer in layers {
+        let stroke = shapes.iter()
+            .find(|s| !options.group_layers || s.layer.as_deref() == layer)
+            .and_then(|s| s.stroke)
+            .unwrap_or(options.stroke);
+        writer.start_group(layer, stroke)?;
+        let members = shapes.iter()
+            .filter(|s| !options.group_layers || s.layer.as_deref() == layer);
+        for shape in members {
+            let attributes = match shape.stroke {
+                Some(color) if color != stroke => format!(r#" stroke="{}""#, color.to_hex()),
+                _ => String::new(),
+            };
+            for polyline in &shape.polylines {
+                writer.polyline(polyline, &attributes)?;
+            }
+        }
+        writer.end_group()?;
+    }
+
+    writer.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Polyline {
+        vec![(0.0, 0.0).into(), (10.0, 0.0).into(), (10.0, 10.0).into(), (0.0, 0.0).into()]
+    }
+
+    #[test]
+    fn test_write_polylines() {
+        let polylines = vec![square(), vec![(5.0, 5.0).into(), (20.0, 2.5).into()]];
+        let mut out = Vec::new();
+        write_polylines(&mut out, &polylines, &SvgOptions::default()).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+
+        assert!(svg.contains(r#"width="20mm" height="10mm" viewBox="0 0 20 10""#));
+        assert!(svg.contains(r#"<polyline points="0,0 10,0 10,10 0,0"/>"#));
+        assert!(svg.contains(r#"<polyline points="5,5 20,2.5"/>"#));
+        assert!(svg.trim_end().ends_with("</g>\n</svg>"));
+    }
+
+    #[test]
+    fn test_write_paths_with_size_and_precision() {
+        let polylines = vec![square(), vec![(1.23456, 0.0).into(), (2.0, 1.0).into()]];
+        let options = SvgOptions {
+            precision: 1,
+            size: Some((210.0, 297.0)),
+            element: Element::Path,
+            ..SvgOptions::default()
+        };
+        let mut out = Vec::new();
+        write_polylines(&mut out, &polylines, &options).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+
+        assert!(svg.contains(r#"width="210mm" height="297mm" viewBox="0 0 210 297""#));
+        assert!(svg.contains(r#"<path d="M0,0 L10,0 L10,10 Z"/>"#));
+        assert!(svg.contains(r#"<path d="M1.2,0 L2,1"/>"#));
+    }
+
+    #[test]
+    fn test_write_shapes_grouped() {
+        let line = |x: f64| vec![(x, 0.0).into(), (x, 1.0).into()];
+        let shapes = vec![
+            Shape { layer: Some("pen1".into()), stroke: Some(Color::new(255, 0, 0)), polylines: vec![line(0.0)], ..Shape::default() },
+            Shape { layer: Some("pen2".into()), stroke: None, polylines: vec![line(1.0)], ..Shape::default() },
+            Shape { layer: Some("pen1".into()), stroke: Some(Color::new(0, 0, 255)), polylines: vec![line(2.0)], ..Shape::default() },
+        ];
+        let mut out = Vec::new();
+        write_shapes(&mut out, &shapes, &SvgOptions::default()).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+
+        let pen1 = svg.find(r##"<g id="pen1" fill="none" stroke="#ff0000""##).unwrap();
+        let pen2 = svg.find(r##"<g id="pen2" fill="none" stroke="#000000""##).unwrap();
+        assert!(pen1 < pen2);
+        let blue = svg.find(r##"<polyline points="2,0 2,1" stroke="#0000ff"/>"##).unwrap();
+        assert!(blue < pen2);
+        assert_eq!(svg.matches("<g ").count(), 2);
+    }
+}
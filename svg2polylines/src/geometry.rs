@@ -0,0 +1,231 @@
+//! Basic measurements of polylines.
+//!
+//! The `Geometry` trait is implemented both for single polylines and for
+//! collections of polylines like the result of `parse`, e.g.
+//! `polylines.length()` is the total length of all polylines.
+//!
+//! Areas are signed like in `nesting`: positive for counter-clockwise
+//! polylines in a coordinate system with the y axis pointing up (which is
+//! clockwise on the screen, where the y axis of SVG points down). Only
+//! closed polylines enclose an area.
+
+use {CoordinatePair, FillRule, Polyline};
+use util::{self, closest_point, distance, Segment};
+
+/// Measurements of a polyline or a collection of polylines.
+pub trait Geometry {
+    /// The length, or the sum of the lengths of a collection.
+    fn length(&self) -> f64;
+
+    /// The axis aligned bounding box `(min, max)`, or `None` without points.
+    fn bounds(&self) -> Option<(CoordinatePair, CoordinatePair)>;
+
+    /// The signed area enclosed by closed polylines, see the module
+    /// documentation. Holes oriented opposite to their outline are
+    /// subtracted.
+    fn signed_area(&self) -> f64;
+
+    /// The center of mass of the area enclosed by closed polylines. Without
+    /// an enclosed area, this is the center of mass of the lines instead,
+    /// and for single points their average. `None` without points.
+    fn centroid(&self) -> Option<CoordinatePair>;
+
+    /// Whether the polyline is closed, or all polylines of a collection are.
+    fn is_closed(&self) -> bool;
+
+    /// Whether the point is inside of the region enclosed by the polylines,
+    /// according to the fill rule. Like in SVG, open polylines are
+    /// implicitly closed.
+    fn contains_point(&self, p: &CoordinatePair, fill_rule: FillRule) -> bool;
+
+    /// The point on the polylines closest to `p`, or `None` without points.
+    fn nearest_point(&self, p: &CoordinatePair) -> Option<CoordinatePair>;
+}
+
+/// The totals needed for centroids: the signed area and the moments of the
+/// area, and the length and the moments of the lines.
+#[derive(Debug, Default)]
+struct Moments {
+    area: f64,
+    area_x: f64,
+    area_y: f64,
+    length: f64,
+    length_x: f64,
+    length_y: f64,
+    points: usize,
+    point_x: f64,
+    point_y: f64,
+}
+
+impl Moments {
+    fn add(&mut self, polyline: &[CoordinatePair]) {
+        if util::is_closed(polyline) {
+            for w in polyline.windows(2) {
+                let cross = w[0].x * w[1].y - w[1].x * w[0].y;
+                self.area += cross / 2.0;
+                self.area_x += (w[0].x + w[1].x) * cross / 6.0;
+                self.area_y += (w[0].y + w[1].y) * cross / 6.0;
+            }
+        }
+        for w in polyline.windows(2) {
+            let length = distance(&w[0], &w[1]);
+            self.length += length;
+            self.length_x += (w[0].x + w[1].x) * length / 2.0;
+            self.length_y += (w[0].y + w[1].y) * length / 2.0;
+        }
+        for p in polyline {
+            self.points += 1;
+            self.point_x += p.x;
+            self.point_y += p.y;
+        }
+    }
+
+    fn centroid(&self) -> Option<CoordinatePair> {
+        if self.area != 0.0 {
+            Some(CoordinatePair::new(self.area_x / self.area, self.area_y / self.area))
+        } else if self.length > 0.0 {
+            Some(CoordinatePair::new(self.length_x / self.length, self.length_y / self.length))
+        } else if self.points > 0 {
+            Some(CoordinatePair::new(self.point_x / self.points as f64, self.point_y / self.points as f64))
+        } else {
+            None
+        }
+    }
+}
+
+/// Return the edges of the polyline, implicitly closed.
+fn closing_edges(polyline: &[CoordinatePair]) -> impl Iterator<Item=Segment> + '_ {
+    let n = polyline.len();
+    (0..n).map(move |i| (polyline[i], polyline[(i + 1) % n]))
+}
+
+impl Geometry for [CoordinatePair] {
+    fn length(&self) -> f64 {
+        self.windows(2).map(|w| distance(&w[0], &w[1])).sum()
+    }
+
+    fn bounds(&self) -> Option<(CoordinatePair, CoordinatePair)> {
+        util::bounds(self)
+    }
+
+    fn signed_area(&self) -> f64 {
+        if util::is_closed(self) { util::signed_area(self) } else { 0.0 }
+    }
+
+    fn centroid(&self) -> Option<CoordinatePair> {
+        let mut moments = Moments::default();
+        moments.add(self);
+        moments.centroid()
+    }
+
+    fn is_closed(&self) -> bool {
+        util::is_closed(self)
+    }
+
+    fn contains_point(&self, p: &CoordinatePair, fill_rule: FillRule) -> bool {
+        let edges: Vec<Segment> = closing_edges(self).collect();
+        fill_rule.is_inside(util::winding(edges.iter(), p))
+    }
+
+    fn nearest_point(&self, p: &CoordinatePair) -> Option<CoordinatePair> {
+        if self.len() == 1 {
+            return Some(self[0]);
+        }
+        self.windows(2)
+            .map(|w| closest_point(p, &w[0], &w[1]))
+            .min_by(|a, b| distance(a, p).total_cmp(&distance(b, p)))
+    }
+}
+
+impl Geometry for [Polyline] {
+    fn length(&self) -> f64 {
+        self.iter().map(|p| p.length()).sum()
+    }
+
+    fn bounds(&self) -> Option<(CoordinatePair, CoordinatePair)> {
+        util::bounds(self.iter().flat_map(|p| p.iter()))
+    }
+
+    fn signed_area(&self) -> f64 {
+        self.iter().map(|p| p.signed_area()).sum()
+    }
+
+    fn centroid(&self) -> Option<CoordinatePair> {
+        let mut moments = Moments::default();
+        for polyline in self {
+            moments.add(polyline);
+        }
+        moments.centroid()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.iter().all(|p| util::is_closed(p))
+    }
+
+    fn contains_point(&self, p: &CoordinatePair, fill_rule: FillRule) -> bool {
+        let edges: Vec<Segment> = self.iter().flat_map(|polyline| closing_edges(polyline)).collect();
+        fill_rule.is_inside(util::winding(edges.iter(), p))
+    }
+
+    fn nearest_point(&self, p: &CoordinatePair) -> Option<CoordinatePair> {
+        self.iter()
+            .filter_map(|polyline| polyline.nearest_point(p))
+            .min_by(|a, b| distance(a, p).total_cmp(&distance(b, p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polyline {
+        polyline(&[(x, y), (x + size, y), (x + size, y + size), (x, y + size), (x, y)])
+    }
+
+    #[test]
+    fn test_polyline() {
+        let square = square(0.0, 0.0, 2.0);
+        assert_eq!(square.length(), 8.0);
+        assert_eq!(square.bounds(), Some(((0.0, 0.0).into(), (2.0, 2.0).into())));
+        assert_eq!(square.signed_area(), 4.0);
+        assert_eq!(square.centroid(), Some((1.0, 1.0).into()));
+        assert!(square.is_closed());
+        assert!(square.contains_point(&(1.0, 1.0).into(), FillRule::NonZero));
+        assert!(!square.contains_point(&(3.0, 1.0).into(), FillRule::NonZero));
+        assert_eq!(square.nearest_point(&(1.0, 5.0).into()), Some((1.0, 2.0).into()));
+
+        let line = polyline(&[(0.0, 0.0), (4.0, 0.0), (4.0, 2.0)]);
+        assert_eq!(line.signed_area(), 0.0);
+        assert!(!line.is_closed());
+        // The center of mass of the lines, weighted by length
+        let centroid = line.centroid().unwrap();
+        assert!(distance(&centroid, &(8.0 / 3.0, 1.0 / 3.0).into()) < 1e-9);
+        // Open polylines are implicitly closed
+        assert!(line.contains_point(&(3.0, 0.5).into(), FillRule::NonZero));
+
+        assert_eq!(polyline(&[(1.0, 2.0)]).centroid(), Some((1.0, 2.0).into()));
+        assert_eq!(polyline(&[]).centroid(), None);
+        assert_eq!(polyline(&[]).nearest_point(&(0.0, 0.0).into()), None);
+    }
+
+    #[test]
+    fn test_collection() {
+        let mut hole = square(1.0, 1.0, 2.0);
+        hole.reverse();
+        let polylines = [square(0.0, 0.0, 4.0), hole, polyline(&[(5.0, 0.0), (5.0, 4.0)])];
+        assert_eq!(polylines.length(), 16.0 + 8.0 + 4.0);
+        assert_eq!(polylines.bounds(), Some(((0.0, 0.0).into(), (5.0, 4.0).into())));
+        assert_eq!(polylines.signed_area(), 12.0);
+        let centroid = polylines[..2].centroid().unwrap();
+        assert!(distance(&centroid, &(2.0, 2.0).into()) < 1e-9);
+        assert!(!polylines.is_closed());
+        assert!(polylines[..2].is_closed());
+        assert!(!polylines[..2].contains_point(&(2.0, 2.0).into(), FillRule::NonZero));
+        assert!(polylines[..2].contains_point(&(0.5, 2.0).into(), FillRule::NonZero));
+        assert_eq!(polylines.nearest_point(&(5.5, 2.0).into()), Some((5.0, 2.0).into()));
+    }
+}
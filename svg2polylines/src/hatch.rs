@@ -0,0 +1,447 @@
+//! Fill closed shapes with hatch lines.
+//!
+//! Pen plotters can't fill areas, so filled shapes are drawn as a series of
+//! parallel lines instead. The inside of a shape is determined with its fill
+//! rule, so holes and self-intersecting outlines are handled the same way an
+//! SVG renderer would. Like in SVG, open polylines are implicitly closed.
+//!
+//! Hatch lines are placed on a global grid, so that adjacent shapes hatched
+//! with the same options line up.
+//!
+//! `shade_shape` chooses the line spacing and number of hatch layers from
+//! the fill color and opacity of a shape, so that lighter fills are drawn
+//! with less ink.
+
+use {CoordinatePair, FillRule, Polyline, Shape};
+use util::{crossings, intersection, winding, Segment};
+
+/// The maximum length of a connection between two hatch lines, in multiples
+/// of the line spacing.
+const MAX_CONNECTION_LENGTH: f64 = 3.0;
+
+/// Options for hatch fills.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HatchOptions {
+    /// The distance between hatch lines.
+    pub spacing: f64,
+    /// The angle of the hatch lines to the x axis in degrees.
+    pub angle: f64,
+    /// Whether to add a second set of hatch lines perpendicular to the
+    /// first one.
+    pub cross_hatch: bool,
+    /// Whether to connect neighbouring hatch lines into zig-zag polylines,
+    /// where the connection stays inside the shape.
+    pub connect: bool,
+}
+
+impl Default for HatchOptions {
+    fn default() -> Self {
+        HatchOptions {
+            spacing: 1.0,
+            angle: 45.0,
+            cross_hatch: false,
+            connect: true,
+        }
+    }
+}
+
+/// A rotation between drawing coordinates and hatch coordinates, in which
+/// the hatch lines are horizontal.
+#[derive(Debug, Copy, Clone)]
+struct Rotation {
+    cos: f64,
+    sin: f64,
+}
+
+impl Rotation {
+    fn new(degrees: f64) -> Self {
+        let radians = degrees.to_radians();
+        Rotation { cos: radians.cos(), sin: radians.sin() }
+    }
+
+    fn rotate(self, p: &CoordinatePair) -> CoordinatePair {
+        CoordinatePair::new(p.x * self.cos + p.y * self.sin, -p.x * self.sin + p.y * self.cos)
+    }
+
+    fn rotate_back(self, p: &CoordinatePair) -> CoordinatePair {
+        CoordinatePair::new(p.x * self.cos - p.y * self.sin, p.x * self.sin + p.y * self.cos)
+    }
+}
+
+/// Return the intervals of the horizontal line at `y` that are inside.
+fn spans<'a, I: Iterator<Item=&'a Segment>>(edges: I, y: f64, fill_rule: FillRule) -> Vec<(f64, f64)> {
+    let mut crossings = crossings(edges, y);
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut spans = Vec::new();
+    let mut winding = 0;
+    let mut start = None;
+    for (x, direction) in crossings {
+        winding += direction;
+        match (start, fill_rule.is_inside(winding)) {
+            (None, true) => start = Some(x),
+            (Some(s), false) => {
+                if x > s {
+                    spans.push((s, x));
+                }
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    spans
+}
+
+/// Whether the segment from `p` to `q` crosses the edge from `a` to `b`,
+/// not counting the end points of the segment.
+fn crosses(p: &CoordinatePair, q: &CoordinatePair, a: &CoordinatePair, b: &CoordinatePair) -> bool {
+    intersection(p, q, a, b).is_some_and(|(t, u)| t > 1e-9 && t < 1.0 - 1e-9 && (0.0..=1.0).contains(&u))
+}
+
+/// The hatch lines of a single direction, in hatch coordinates.
+struct Hatching {
+    spacing: f64,
+    /// The index of the first hatch line on the global grid.
+    first: i64,
+    /// The inside intervals of every hatch line.
+    lines: Vec<Vec<(f64, f64)>>,
+    /// The edges overlapping the band between each line and the next one.
+    bands: Vec<Vec<Segment>>,
+    fill_rule: FillRule,
+}
+
+impl Hatching {
+    fn new(edges: &[Segment], spacing: f64, fill_rule: FillRule) -> Self {
+        let (min_y, max_y) = edges.iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &(a, _)| (min.min(a.y), max.max(a.y)));
+        let first = (min_y / spacing).ceil() as i64;
+        let last = (max_y / spacing).floor() as i64;
+        let count = (last - first + 1).max(0) as usize;
+
+        let mut bands = vec![Vec::new(); count];
+        for edge in edges {
+            let (lo, hi) = (edge.0.y.min(edge.1.y), edge.0.y.max(edge.1.y));
+            let from = ((lo / spacing).ceil() as i64 - 1 - first).max(0);
+            let to = ((hi / spacing).floor() as i64 - first).min(count as i64 - 1);
+            for band in from..to + 1 {
+                bands[band as usize].push(*edge);
+            }
+        }
+        let lines = (0..count)
+            .map(|i| {
+                let y = (first + i as i64) as f64 * spacing;
+                spans(bands[i].iter(), y, fill_rule)
+            })
+            .collect();
+
+        Hatching { spacing, first, lines, bands, fill_rule }
+    }
+
+    fn y(&self, line: usize) -> f64 {
+        (self.first + line as i64) as f64 * self.spacing
+    }
+
+    /// Whether the connection from `p` on a line to `q` on the next line
+    /// stays inside. `forward` is true if the connection is at the right end
+    /// of the hatch lines, so that their inside is to the left.
+    fn connectable(&self, line: usize, p: &CoordinatePair, q: &CoordinatePair, forward: bool) -> bool {
+        let edges = &self.bands[line];
+        // Connections often run along the outline, so test a point slightly
+        // towards the inside of the hatch lines
+        let nudge = if forward { -1e-6 } else { 1e-6 } * self.spacing;
+        let mid = CoordinatePair::new((p.x + q.x) / 2.0 + nudge, (p.y + q.y) / 2.0);
+        (q.x - p.x).abs() <= self.spacing * MAX_CONNECTION_LENGTH
+            && self.fill_rule.is_inside(winding(edges.iter(), &mid))
+            && !edges.iter().any(|&(a, b)| crosses(p, q, &a, &b))
+    }
+
+    /// Turn the hatch lines into polylines, alternating the direction of
+    /// consecutive lines and optionally connecting them.
+    fn polylines(&self, connect: bool) -> Vec<Polyline> {
+        let mut used: Vec<Vec<bool>> = self.lines.iter().map(|spans| vec![false; spans.len()]).collect();
+        let mut polylines = Vec::new();
+        for line in 0..self.lines.len() {
+            for i in 0..self.lines[line].len() {
+                if used[line][i] {
+                    continue;
+                }
+                used[line][i] = true;
+                let (mut line, mut forward) = (line, line % 2 == 0);
+                let point = |line: usize, x: f64| CoordinatePair::new(x, self.y(line));
+                let (x0, x1) = self.lines[line][i];
+                let mut polyline = if forward {
+                    vec![point(line, x0), point(line, x1)]
+                } else {
+                    vec![point(line, x1), point(line, x0)]
+                };
+                while connect && line + 1 < self.lines.len() {
+                    let end = polyline[polyline.len() - 1];
+                    let next = self.lines[line + 1].iter().enumerate()
+                        .filter(|&(j, _)| !used[line + 1][j])
+                        .map(|(j, &(x0, x1))| if forward { (j, x1, x0) } else { (j, x0, x1) })
+                        .filter(|&(_, start, _)| self.connectable(line, &end, &point(line + 1, start), forward))
+                        .min_by(|a, b| (a.1 - end.x).abs().total_cmp(&(b.1 - end.x).abs()));
+                    match next {
+                        Some((j, start, finish)) => {
+                            used[line + 1][j] = true;
+                            polyline.push(point(line + 1, start));
+                            polyline.push(point(line + 1, finish));
+                            line += 1;
+                            forward = !forward;
+                        },
+                        None => break,
+                    }
+                }
+                polylines.push(polyline);
+            }
+        }
+        polylines
+    }
+}
+
+/// Hatch lines of a single direction.
+fn hatch_direction(polylines: &[Polyline], fill_rule: FillRule, angle: f64, options: &HatchOptions) -> Vec<Polyline> {
+    let rotation = Rotation::new(angle);
+    let mut edges = Vec::new();
+    for polyline in polylines.iter().filter(|p| p.len() > 2) {
+        let points: Vec<CoordinatePair> = polyline.iter().map(|p| rotation.rotate(p)).collect();
+        for (i, a) in points.iter().enumerate() {
+            edges.push((*a, points[(i + 1) % points.len()]));
+        }
+    }
+    if edges.is_empty() {
+        return Vec::new();
+    }
+    Hatching::new(&edges, options.spacing, fill_rule)
+        .polylines(options.connect)
+        .into_iter()
+        .map(|polyline| polyline.iter().map(|p| rotation.rotate_back(p)).collect())
+        .collect()
+}
+
+/// Fill the area enclosed by the polylines with hatch lines.
+///
+/// All polylines together form a single shape, so that for example a
+/// polyline inside another one is a hole if the fill rule says so.
+pub fn hatch(polylines: &[Polyline], fill_rule: FillRule, options: &HatchOptions) -> Vec<Polyline> {
+    if options.spacing <= 0.0 {
+        return Vec::new();
+    }
+    let mut hatched = hatch_direction(polylines, fill_rule, options.angle, options);
+    if options.cross_hatch {
+        hatched.extend(hatch_direction(polylines, fill_rule, options.angle + 90.0, options));
+    }
+    hatched
+}
+
+/// Fill a shape with hatch lines, using its fill rule.
+pub fn hatch_shape(shape: &Shape, options: &HatchOptions) -> Vec<Polyline> {
+    hatch(&shape.polylines, shape.fill_rule, options)
+}
+
+/// Options for shading fills with hatch lines of varying density.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShadingOptions {
+    /// The line spacing of a layer at full density, as used for black fills.
+    /// This should be about the width of the pen.
+    pub min_spacing: f64,
+    /// The largest line spacing. Layers that would need a wider spacing are
+    /// left out, so very light fills are not hatched at all.
+    pub max_spacing: f64,
+    /// The angle of the first layer of hatch lines in degrees.
+    pub angle: f64,
+    /// The maximum number of layers. Darker fills are drawn with additional
+    /// layers, at angles evenly distributed over 180°.
+    pub layers: usize,
+    /// Whether to connect neighbouring hatch lines into zig-zag polylines.
+    pub connect: bool,
+}
+
+impl Default for ShadingOptions {
+    fn default() -> Self {
+        ShadingOptions {
+            min_spacing: 0.5,
+            max_spacing: 5.0,
+            angle: 45.0,
+            layers: 2,
+            connect: true,
+        }
+    }
+}
+
+/// Return the line spacing of every hatch layer needed for a fill with the
+/// given darkness (0 for white, 1 for black).
+fn layer_spacings(darkness: f64, options: &ShadingOptions) -> Vec<f64> {
+    // The darkness is distributed over the layers, filling one layer to full
+    // density before starting the next one. The darkness of a layer is the
+    // fraction of its area covered by the pen, which is inversely
+    // proportional to the line spacing.
+    let layers = options.layers.max(1);
+    (0..layers)
+        .map(|i| (darkness * layers as f64 - i as f64).clamp(0.0, 1.0))
+        .take_while(|&layer_darkness| layer_darkness > 0.0)
+        .map(|layer_darkness| options.min_spacing / layer_darkness)
+        .take_while(|&spacing| spacing <= options.max_spacing)
+        .collect()
+}
+
+/// Fill a shape with hatch lines whose density matches the luminance and
+/// opacity of its fill color, assuming a black pen on white paper.
+///
+/// Shapes without fill are not hatched.
+pub fn shade_shape(shape: &Shape, options: &ShadingOptions) -> Vec<Polyline> {
+    let darkness = match shape.fill {
+        Some(color) => (1.0 - color.luminance()) * shape.fill_opacity.clamp(0.0, 1.0),
+        None => return Vec::new(),
+    };
+    let layers = options.layers.max(1);
+    layer_spacings(darkness, options).into_iter()
+        .enumerate()
+        .flat_map(|(i, spacing)| {
+            let hatch_options = HatchOptions {
+                spacing,
+                angle: options.angle + i as f64 * 180.0 / layers as f64,
+                cross_hatch: false,
+                connect: options.connect,
+            };
+            hatch_shape(shape, &hatch_options)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Color;
+
+    fn square(min: f64, max: f64, clockwise: bool) -> Polyline {
+        let mut points: Polyline = vec![
+            (min, min).into(), (max, min).into(), (max, max).into(), (min, max).into(), (min, min).into(),
+        ];
+        if clockwise {
+            points.reverse();
+        }
+        points
+    }
+
+    fn horizontal(connect: bool) -> HatchOptions {
+        HatchOptions { angle: 0.0, connect, ..HatchOptions::default() }
+    }
+
+    #[test]
+    fn test_hatch_square() {
+        let hatched = hatch(&[square(0.0, 4.5, false)], FillRule::NonZero, &horizontal(false));
+        assert_eq!(hatched, vec![
+            vec![(0.0, 0.0).into(), (4.5, 0.0).into()],
+            vec![(4.5, 1.0).into(), (0.0, 1.0).into()],
+            vec![(0.0, 2.0).into(), (4.5, 2.0).into()],
+            vec![(4.5, 3.0).into(), (0.0, 3.0).into()],
+            vec![(0.0, 4.0).into(), (4.5, 4.0).into()],
+        ]);
+
+        // Connected into a single zig-zag
+        let hatched = hatch(&[square(0.0, 4.5, false)], FillRule::NonZero, &horizontal(true));
+        assert_eq!(hatched.len(), 1);
+        assert_eq!(hatched[0].len(), 10);
+        assert_eq!(hatched[0][2], (4.5, 1.0).into());
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        // Outer and inner square with the same orientation
+        let shape = [square(0.0, 10.5, false), square(3.5, 6.5, false)];
+        let line = |hatched: &[Polyline], y: f64| -> usize {
+            hatched.iter().flat_map(|p| p.windows(2)).filter(|w| w[0].y == y && w[1].y == y).count()
+        };
+        let nonzero = hatch(&shape, FillRule::NonZero, &horizontal(false));
+        assert_eq!(line(&nonzero, 5.0), 1);
+        let evenodd = hatch(&shape, FillRule::EvenOdd, &horizontal(false));
+        assert_eq!(line(&evenodd, 5.0), 2);
+        assert_eq!(line(&evenodd, 1.0), 1);
+
+        // With opposite orientation, the inner square is a hole either way
+        let shape = [square(0.0, 10.5, false), square(3.5, 6.5, true)];
+        let nonzero = hatch(&shape, FillRule::NonZero, &horizontal(false));
+        assert_eq!(line(&nonzero, 5.0), 2);
+    }
+
+    #[test]
+    fn test_connections_stay_inside() {
+        // U shape: the hatch lines of both arms must not be connected across
+        // the gap between them
+        let u: Polyline = vec![
+            (0.0, 0.0).into(), (9.0, 0.0).into(), (9.0, 9.5).into(), (6.0, 9.5).into(), (6.0, 3.5).into(),
+            (3.0, 3.5).into(), (3.0, 9.5).into(), (0.0, 9.5).into(), (0.0, 0.0).into(),
+        ];
+        let hatched = hatch(&[u], FillRule::NonZero, &horizontal(true));
+        for polyline in &hatched {
+            for w in polyline.windows(2) {
+                let mid = CoordinatePair::new((w[0].x + w[1].x) / 2.0, (w[0].y + w[1].y) / 2.0);
+                assert!(!(mid.x > 3.0 && mid.x < 6.0 && mid.y > 3.5), "{:?} crosses the gap", w);
+            }
+        }
+        assert!(hatched.len() >= 2);
+        assert!(hatched.len() < 10);
+    }
+
+    #[test]
+    fn test_cross_hatch_and_angle() {
+        let options = HatchOptions { cross_hatch: true, connect: false, ..HatchOptions::default() };
+        let hatched = hatch(&[square(0.0, 10.0, false)], FillRule::NonZero, &options);
+        let diagonal = |p: &Polyline, sign: f64| ((p[1].y - p[0].y) - sign * (p[1].x - p[0].x)).abs() < 1e-9;
+        let up = hatched.iter().filter(|p| diagonal(p, 1.0)).count();
+        let down = hatched.iter().filter(|p| diagonal(p, -1.0)).count();
+        assert_eq!(up + down, hatched.len());
+        assert!(up > 10 && down > 10);
+        for p in hatched.iter().flat_map(|p| p.iter()) {
+            assert!(p.x > -1e-9 && p.x < 10.0 + 1e-9 && p.y > -1e-9 && p.y < 10.0 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_hatch_shape() {
+        let shape = Shape {
+            fill_rule: FillRule::EvenOdd,
+            polylines: vec![square(0.0, 10.5, false), square(3.5, 6.5, false)],
+            ..Shape::default()
+        };
+        assert_eq!(
+            hatch_shape(&shape, &horizontal(false)),
+            hatch(&shape.polylines, FillRule::EvenOdd, &horizontal(false)),
+        );
+    }
+
+    #[test]
+    fn test_layer_spacings() {
+        let options = ShadingOptions::default();
+        assert_eq!(layer_spacings(1.0, &options), vec![0.5, 0.5]);
+        assert_eq!(layer_spacings(0.75, &options), vec![0.5, 1.0]);
+        assert_eq!(layer_spacings(0.25, &options), vec![1.0]);
+        assert!(layer_spacings(0.01, &options).is_empty());
+        assert!(layer_spacings(0.0, &options).is_empty());
+
+        let options = ShadingOptions { layers: 1, ..ShadingOptions::default() };
+        assert_eq!(layer_spacings(0.25, &options), vec![2.0]);
+    }
+
+    #[test]
+    fn test_shade_shape() {
+        let options = ShadingOptions { angle: 0.0, connect: false, ..ShadingOptions::default() };
+        let shape = |fill: Option<Color>, fill_opacity: f64| Shape {
+            fill,
+            fill_opacity,
+            polylines: vec![square(0.0, 20.0, false)],
+            ..Shape::default()
+        };
+        let length = |polylines: Vec<Polyline>| -> f64 {
+            polylines.iter().flat_map(|p| p.windows(2)).map(|w| (w[1].x - w[0].x).hypot(w[1].y - w[0].y)).sum()
+        };
+        let black = length(shade_shape(&shape(Some(Color::new(0, 0, 0)), 1.0), &options));
+        let gray = length(shade_shape(&shape(Some(Color::new(188, 188, 188)), 1.0), &options));
+        let transparent = length(shade_shape(&shape(Some(Color::new(0, 0, 0)), 0.5), &options));
+        assert!(black > 0.0);
+        assert!((gray / black - 0.5).abs() < 0.05, "{} / {}", gray, black);
+        assert!((transparent / black - 0.5).abs() < 0.05, "{} / {}", transparent, black);
+        assert!(shade_shape(&shape(Some(Color::new(255, 255, 255)), 1.0), &options).is_empty());
+        assert!(shade_shape(&shape(None, 1.0), &options).is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+//! Place drawings on standard paper sizes.
+//!
+//! SVG files come in all sizes, but a plot has to fit the paper in the
+//! plotter. The layout step scales the drawing to fit the paper within its
+//! margins, aligns it on the page, and moves the origin to the corner where
+//! the machine has its home position.
+//!
+//! Paper sizes are in millimeters, so the drawing is expected to use
+//! millimeters as well. Like in SVG, the y axis of the drawing points down.
+
+use std::str;
+
+use {CoordinatePair, Polyline, Shape};
+use util::bounds;
+
+/// A paper size.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Paper {
+    /// ISO 216 A0, 841 × 1189 mm.
+    A0,
+    /// ISO 216 A1, 594 × 841 mm.
+    A1,
+    /// ISO 216 A2, 420 × 594 mm.
+    A2,
+    /// ISO 216 A3, 297 × 420 mm.
+    A3,
+    /// ISO 216 A4, 210 × 297 mm.
+    #[default]
+    A4,
+    /// ISO 216 A5, 148 × 210 mm.
+    A5,
+    /// ISO 216 A6, 105 × 148 mm.
+    A6,
+    /// ANSI A, 8.5 × 11 in.
+    Letter,
+    /// 8.5 × 14 in.
+    Legal,
+    /// ANSI B, 11 × 17 in.
+    Tabloid,
+    /// ANSI C, 17 × 22 in.
+    AnsiC,
+    /// ANSI D, 22 × 34 in.
+    AnsiD,
+    /// A custom size, width and height in millimeters.
+    Custom(f64, f64),
+}
+
+impl Paper {
+    /// Return the width and height of the paper in portrait orientation, in
+    /// millimeters.
+    pub fn size(&self) -> (f64, f64) {
+        const INCH: f64 = 25.4;
+        let (width, height) = match *self {
+            Paper::A0 => (841.0, 1189.0),
+            Paper::A1 => (594.0, 841.0),
+            Paper::A2 => (420.0, 594.0),
+            Paper::A3 => (297.0, 420.0),
+            Paper::A4 => (210.0, 297.0),
+            Paper::A5 => (148.0, 210.0),
+            Paper::A6 => (105.0, 148.0),
+            Paper::Letter => (8.5 * INCH, 11.0 * INCH),
+            Paper::Legal => (8.5 * INCH, 14.0 * INCH),
+            Paper::Tabloid => (11.0 * INCH, 17.0 * INCH),
+            Paper::AnsiC => (17.0 * INCH, 22.0 * INCH),
+            Paper::AnsiD => (22.0 * INCH, 34.0 * INCH),
+            Paper::Custom(width, height) => (width, height),
+        };
+        (width.min(height), width.max(height))
+    }
+}
+
+impl str::FromStr for Paper {
+    type Err = String;
+
+    /// Parse a paper name like `a4` or `letter`, or a custom size like
+    /// `300x200` (in millimeters).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        let paper = match name.as_str() {
+            "a0" => Paper::A0,
+            "a1" => Paper::A1,
+            "a2" => Paper::A2,
+            "a3" => Paper::A3,
+            "a4" => Paper::A4,
+            "a5" => Paper::A5,
+            "a6" => Paper::A6,
+            "letter" | "ansi-a" => Paper::Letter,
+            "legal" => Paper::Legal,
+            "tabloid" | "ledger" | "ansi-b" => Paper::Tabloid,
+            "ansi-c" => Paper::AnsiC,
+            "ansi-d" => Paper::AnsiD,
+            _ => {
+                let mut parts = name.splitn(2, 'x').map(|v| v.trim().parse::<f64>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(width)), Some(Ok(height))) if width > 0.0 && height > 0.0 => Paper::Custom(width, height),
+                    _ => return Err(format!("Invalid paper size: {}", s)),
+                }
+            },
+        };
+        Ok(paper)
+    }
+}
+
+/// The orientation of the paper.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum PaperOrientation {
+    Portrait,
+    Landscape,
+    /// Landscape if the drawing is wider than it is tall, portrait otherwise.
+    #[default]
+    Auto,
+}
+
+/// How to scale the drawing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Fit {
+    /// Scale the drawing up or down to fill the printable area.
+    #[default]
+    Fit,
+    /// Scale the drawing down if it is larger than the printable area.
+    Shrink,
+    /// Keep the size of the drawing.
+    None,
+}
+
+/// The alignment of the drawing within the printable area, along one axis.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Align {
+    /// Left or top.
+    Start,
+    #[default]
+    Center,
+    /// Right or bottom.
+    End,
+}
+
+/// The corner of the paper at the origin of the machine. The axes of the
+/// result point from this corner into the page, so all coordinates on the
+/// page are positive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Origin {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Options for the page layout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutOptions {
+    pub paper: Paper,
+    pub orientation: PaperOrientation,
+    /// The distance between the printable area and the edges of the paper.
+    pub margin: f64,
+    pub fit: Fit,
+    pub align_x: Align,
+    pub align_y: Align,
+    pub origin: Origin,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            paper: Paper::A4,
+            orientation: PaperOrientation::Auto,
+            margin: 10.0,
+            fit: Fit::Fit,
+            align_x: Align::Center,
+            align_y: Align::Center,
+            origin: Origin::TopLeft,
+        }
+    }
+}
+
+/// A layout computed for a drawing, mapping drawing coordinates to page
+/// coordinates.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Layout {
+    /// The width and height of the page in its chosen orientation.
+    pub page_size: (f64, f64),
+    /// The factor the drawing is scaled by.
+    pub scale: f64,
+    /// The translation applied after scaling, before moving the origin.
+    offset: (f64, f64),
+    origin: Origin,
+}
+
+impl Layout {
+    /// Compute the layout for a drawing with the given bounding box.
+    pub fn new(min: CoordinatePair, max: CoordinatePair, options: &LayoutOptions) -> Self {
+        let (width, height) = (max.x - min.x, max.y - min.y);
+        let (short, long) = options.paper.size();
+        let landscape = match options.orientation {
+            PaperOrientation::Portrait => false,
+            PaperOrientation::Landscape => true,
+            PaperOrientation::Auto => width > height,
+        };
+        let page_size = if landscape { (long, short) } else { (short, long) };
+        let area = (
+            (page_size.0 - 2.0 * options.margin).max(0.0),
+            (page_size.1 - 2.0 * options.margin).max(0.0),
+        );
+
+        // The factor to fit the drawing, ignoring degenerate dimensions
+        let ratio = |available: f64, size: f64| if size > 0.0 { available / size } else { f64::INFINITY };
+        let fit = ratio(area.0, width).min(ratio(area.1, height));
+        let fit = if fit.is_finite() { fit } else { 1.0 };
+        let scale = match options.fit {
+            Fit::Fit => fit,
+            Fit::Shrink => fit.min(1.0),
+            Fit::None => 1.0,
+        };
+
+        let position = |align: Align, available: f64, size: f64| {
+            let fraction = match align {
+                Align::Start => 0.0,
+                Align::Center => 0.5,
+                Align::End => 1.0,
+            };
+            options.margin + fraction * (available - size * scale)
+        };
+        let offset = (
+            position(options.align_x, area.0, width) - min.x * scale,
+            position(options.align_y, area.1, height) - min.y * scale,
+        );
+        Layout { page_size, scale, offset, origin: options.origin }
+    }
+
+    /// Map a point of the drawing onto the page.
+    pub fn apply(&self, p: &CoordinatePair) -> CoordinatePair {
+        let (x, y) = (p.x * self.scale + self.offset.0, p.y * self.scale + self.offset.1);
+        let (width, height) = self.page_size;
+        match self.origin {
+            Origin::TopLeft => CoordinatePair::new(x, y),
+            Origin::TopRight => CoordinatePair::new(width - x, y),
+            Origin::BottomLeft => CoordinatePair::new(x, height - y),
+            Origin::BottomRight => CoordinatePair::new(width - x, height - y),
+        }
+    }
+}
+
+/// Place the polylines on the page, see `LayoutOptions`.
+pub fn layout(polylines: Vec<Polyline>, options: &LayoutOptions) -> Vec<Polyline> {
+    let (min, max) = match bounds(polylines.iter().flat_map(|p| p.iter())) {
+        Some(b) => b,
+        None => return polylines,
+    };
+    let layout = Layout::new(min, max, options);
+    polylines.iter()
+        .map(|polyline| polyline.iter().map(|p| layout.apply(p)).collect())
+        .collect()
+}
+
+/// Place the shapes on the page as a whole, see `layout`.
+pub fn layout_shapes(mut shapes: Vec<Shape>, options: &LayoutOptions) -> Vec<Shape> {
+    let (min, max) = match bounds(shapes.iter().flat_map(|s| s.polylines.iter()).flat_map(|p| p.iter())) {
+        Some(b) => b,
+        None => return shapes,
+    };
+    let layout = Layout::new(min, max, options);
+    for shape in &mut shapes {
+        for polyline in &mut shape.polylines {
+            for p in polyline.iter_mut() {
+                *p = layout.apply(p);
+            }
+        }
+    }
+    shapes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::distance;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_paper() {
+        assert_eq!("A3".parse(), Ok(Paper::A3));
+        assert_eq!(" Letter ".parse(), Ok(Paper::Letter));
+        assert_eq!(Paper::A3.size(), (297.0, 420.0));
+        assert_eq!("300x200".parse::<Paper>().map(|p| p.size()), Ok((200.0, 300.0)));
+        assert!("a4x".parse::<Paper>().is_err());
+    }
+
+    #[test]
+    fn test_fit_and_center() {
+        // A wide drawing is placed on landscape A4 with 10 mm margins
+        let drawing = vec![polyline(&[(10.0, 10.0), (110.0, 10.0), (110.0, 60.0)])];
+        let result = layout(drawing.clone(), &LayoutOptions::default());
+        // Limited by the width of 277 mm, so scaled by 2.77
+        let expected = polyline(&[(10.0, 35.75), (287.0, 35.75), (287.0, 174.25)]);
+        for (p, q) in result[0].iter().zip(&expected) {
+            assert!(distance(p, q) < 1e-9, "{:?} != {:?}", p, q);
+        }
+
+        let options = LayoutOptions {
+            orientation: PaperOrientation::Portrait,
+            fit: Fit::None,
+            align_x: Align::Start,
+            align_y: Align::End,
+            origin: Origin::BottomLeft,
+            ..LayoutOptions::default()
+        };
+        let result = layout(drawing.clone(), &options);
+        assert_eq!(result, vec![polyline(&[(10.0, 60.0), (110.0, 60.0), (110.0, 10.0)])]);
+
+        // Shrinking leaves small drawings alone
+        let options = LayoutOptions { fit: Fit::Shrink, align_x: Align::Start, align_y: Align::Start, ..LayoutOptions::default() };
+        assert_eq!(layout(drawing.clone(), &options), vec![polyline(&[(10.0, 10.0), (110.0, 10.0), (110.0, 60.0)])]);
+    }
+
+    #[test]
+    fn test_origin() {
+        let drawing = vec![polyline(&[(0.0, 0.0), (10.0, 10.0)])];
+        let options = LayoutOptions {
+            paper: Paper::Custom(100.0, 100.0),
+            margin: 0.0,
+            fit: Fit::None,
+            align_x: Align::Start,
+            align_y: Align::Start,
+            origin: Origin::TopRight,
+            ..LayoutOptions::default()
+        };
+        assert_eq!(layout(drawing, &options), vec![polyline(&[(100.0, 0.0), (90.0, 10.0)])]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Lead-in, lead-out and overcut for closed polylines.
+//!
+//! Lasers and knives leave a mark where they start and stop cutting, e.g. a
+//! burn spot while the laser accelerates, or a sliver that is not quite cut
+//! through. Moving onto and off the contour with a short lead next to it
+//! keeps these marks off the contour, and cutting past the start point
+//! (overcut) makes sure that the contour is cut through completely.
+
+use std::f64::consts::FRAC_PI_2;
+
+use {CoordinatePair, Polyline};
+use util::{closed_section, cumulative_lengths, distance, is_closed, signed_area};
+
+/// The shape of a lead-in or lead-out move.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Lead {
+    #[default]
+    None,
+    /// A straight line of the given length, perpendicular to the contour.
+    Linear(f64),
+    /// A quarter circle with the given radius, tangent to the contour.
+    Arc(f64),
+}
+
+/// The side of the contour the leads are on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Side {
+    /// Inside the shape, e.g. for cutting holes.
+    Inside,
+    /// Outside the shape, e.g. for cutting parts.
+    #[default]
+    Outside,
+}
+
+/// Options for leads and overcut.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LeadOptions {
+    pub lead_in: Lead,
+    pub lead_out: Lead,
+    pub side: Side,
+    /// The distance to continue cutting past the start of the contour.
+    pub overcut: f64,
+    /// The maximum deviation of arc leads from a true arc.
+    pub tolerance: f64,
+}
+
+impl Default for LeadOptions {
+    fn default() -> Self {
+        LeadOptions {
+            lead_in: Lead::Linear(1.0),
+            lead_out: Lead::None,
+            side: Side::Outside,
+            overcut: 0.0,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Return the unit direction from `a` to `b`.
+fn direction(a: &CoordinatePair, b: &CoordinatePair) -> (f64, f64) {
+    let length = distance(a, b);
+    ((b.x - a.x) / length, (b.y - a.y) / length)
+}
+
+/// Return the points of a lead at `point`, where the contour has the unit
+/// direction `tangent`, on the side of the unit `normal`. A lead-in ends at
+/// the point, a lead-out starts there.
+fn lead(lead: Lead, point: &CoordinatePair, tangent: (f64, f64), normal: (f64, f64), lead_in: bool, tolerance: f64) -> Polyline {
+    let mut points = match lead {
+        Lead::None => vec![*point],
+        Lead::Linear(length) => vec![
+            CoordinatePair::new(point.x + normal.0 * length, point.y + normal.1 * length),
+            *point,
+        ],
+        Lead::Arc(radius) => {
+            // The arc around a center next to the point, from a quarter turn
+            // before the point to the point
+            let center = CoordinatePair::new(point.x + normal.0 * radius, point.y + normal.1 * radius);
+            let max_step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+            let steps = (FRAC_PI_2 / max_step.max(1e-3)).ceil().max(1.0) as usize;
+            // The tangent points backwards for a lead-out, which is built
+            // backwards as well
+            let sign = if lead_in { 1.0 } else { -1.0 };
+            (0..steps + 1)
+                .map(|k| {
+                    let angle = FRAC_PI_2 * (k as f64 / steps as f64 - 1.0);
+                    let (along, across) = (sign * radius * angle.sin(), -radius * angle.cos());
+                    CoordinatePair::new(
+                        center.x + tangent.0 * along + normal.0 * across,
+                        center.y + tangent.1 * along + normal.1 * across,
+                    )
+                })
+                .collect()
+        },
+    };
+    if !lead_in {
+        points.reverse();
+    }
+    points
+}
+
+/// Add leads and overcut to a closed polyline. Other polylines are returned
+/// unchanged.
+///
+/// The cut starts in the middle of the first segment of the polyline, so
+/// that the leads do not run along a neighbouring segment at a corner. The
+/// result starts with the lead-in, follows the polyline from there around to
+/// the same point again and on for the overcut, and ends with the lead-out.
+pub fn add_lead(polyline: &[CoordinatePair], options: &LeadOptions) -> Polyline {
+    if !is_closed(polyline) {
+        return polyline.to_vec();
+    }
+    let first = match polyline.windows(2).position(|w| w[0] != w[1]) {
+        Some(i) => i + 1,
+        None => return polyline.to_vec(),
+    };
+    let (a, b) = (polyline[first - 1], polyline[first]);
+    let middle = CoordinatePair::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+    let mut contour = vec![middle];
+    contour.extend_from_slice(&polyline[first..]);
+    contour.push(middle);
+    let polyline = &contour[..];
+    let lengths = cumulative_lengths(polyline);
+    let perimeter = lengths[lengths.len() - 1];
+
+    // The inside is on the left of counter-clockwise polylines
+    let left = (signed_area(polyline) > 0.0) == (options.side == Side::Inside);
+    let normal = |(dx, dy): (f64, f64)| if left { (-dy, dx) } else { (dy, -dx) };
+
+    // Leaving the start and arriving at the end of the cut
+    let overcut = options.overcut.clamp(0.0, perimeter);
+    let departing = (1..polyline.len())
+        .find(|&i| lengths[i] > 0.0)
+        .map(|i| direction(&polyline[0], &polyline[i]))
+        .unwrap();
+    let mut cut = polyline.to_vec();
+    if overcut > 0.0 {
+        cut.extend(closed_section(polyline, &lengths, 0.0, overcut).into_iter().skip(1));
+    }
+    let end = cut[cut.len() - 1];
+    let arriving = cut.iter().rev().find(|p| **p != end).map(|p| direction(p, &end)).unwrap();
+
+    let mut result = lead(options.lead_in, &polyline[0], departing, normal(departing), true, options.tolerance);
+    result.extend(cut.into_iter().skip(1));
+    result.extend(lead(options.lead_out, &end, arriving, normal(arriving), false, options.tolerance).into_iter().skip(1));
+    result
+}
+
+/// Add leads and overcut to all closed polylines, see `add_lead`.
+pub fn add_leads(polylines: Vec<Polyline>, options: &LeadOptions) -> Vec<Polyline> {
+    polylines.iter().map(|p| add_lead(p, options)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::segment_distance;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    fn square() -> Polyline {
+        polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)])
+    }
+
+    fn close(a: &CoordinatePair, b: (f64, f64)) -> bool {
+        distance(a, &b.into()) < 1e-9
+    }
+
+    #[test]
+    fn test_linear_leads_and_overcut() {
+        let options = LeadOptions {
+            lead_in: Lead::Linear(2.0),
+            lead_out: Lead::Linear(1.0),
+            side: Side::Inside,
+            overcut: 3.0,
+            ..LeadOptions::default()
+        };
+        let result = add_lead(&square(), &options);
+        // The cut starts in the middle of the first segment, away from the
+        // corners, and the leads are perpendicular to it
+        assert_eq!(result, polyline(&[
+            (5.0, 2.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0), (5.0, 0.0), (8.0, 0.0),
+            (8.0, 1.0),
+        ]));
+        // The leads stay off the contour
+        let contour = square();
+        for p in &[result[0], result[result.len() - 1]] {
+            let d = contour.windows(2).map(|w| segment_distance(p, &w[0], &w[1])).fold(f64::INFINITY, f64::min);
+            assert!(d >= 1.0);
+        }
+
+        // Outside of a clockwise polyline is on the left
+        let mut clockwise = square();
+        clockwise.reverse();
+        let options = LeadOptions { lead_in: Lead::Linear(2.0), ..LeadOptions::default() };
+        let result = add_lead(&clockwise, &options);
+        assert_eq!(result[0], (-2.0, 5.0).into());
+        assert_eq!(&result[1..], &polyline(&[
+            (0.0, 5.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0), (0.0, 0.0), (0.0, 5.0),
+        ])[..]);
+
+        // Open polylines are not changed
+        let open = polyline(&[(0.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(add_leads(vec![open.clone()], &options), vec![open]);
+    }
+
+    #[test]
+    fn test_arc_leads() {
+        let options = LeadOptions {
+            lead_in: Lead::Arc(1.0),
+            lead_out: Lead::Arc(1.0),
+            ..LeadOptions::default()
+        };
+        let result = add_lead(&square(), &options);
+        // The arcs start and end a radius away from the contour, on the
+        // outside
+        assert!(close(&result[0], (4.0, -1.0)));
+        assert!(close(&result[result.len() - 1], (6.0, -1.0)));
+        let center = CoordinatePair::new(5.0, -1.0);
+        let arc_points = result.iter().take_while(|p| p.y < 0.0).count();
+        assert!(arc_points > 2);
+        for p in result[..arc_points].iter().chain(result.iter().rev().take_while(|p| p.y < 0.0)) {
+            assert!((distance(p, &center) - 1.0).abs() < 1e-9);
+        }
+    }
+}
@@ -7,10 +7,11 @@
 //! Flattening of Bézier curves is done using the
 //! [Lyon](https://github.com/nical/lyon) library.
 //!
-//! **Note: Currently the path style is completely ignored. Only the path itself is
-//! returned.**
+//! **Note: `parse` ignores the path style and only returns the path itself.**
+//! Use `parse_shapes` to additionally get the stroke and fill and the layer
+//! (the outermost `<g>` element) of every path.
 //!
-//! Minimal supported Rust version: 1.16.
+//! Minimal supported Rust version: 1.70.
 //!
 //! FFI bindings for this crate can be found [on
 //! Github](https://github.com/dbrgn/svg2polylines).
@@ -25,14 +26,43 @@ extern crate serde;
 #[cfg(feature="use_serde")]
 #[macro_use] extern crate serde_derive;
 
+use std::collections::HashMap;
 use std::convert;
 use std::mem;
 use std::str;
 
-use svgparser::{path, AttributeId, FromSpan};
-use svgparser::svg::{Tokenizer, Token};
+use svgparser::{path, AttributeId, ElementId, FromSpan, StrSpan};
+use svgparser::svg::{ElementEnd, Name, Tokenizer, Token};
 use lyon_bezier::{QuadraticBezierSegment, CubicBezierSegment, Point};
 
+mod style;
+mod util;
+pub mod cleanup;
+pub mod clip;
+pub mod contour;
+pub mod drag_knife;
+pub mod export;
+pub mod geometry;
+pub mod hatch;
+pub mod layout;
+pub mod lead;
+pub mod optimize;
+pub mod merge;
+pub mod nesting;
+pub mod occlusion;
+pub mod offset;
+pub mod overlap;
+pub mod simplify;
+pub mod tabs;
+pub mod tiling;
+pub mod transform;
+
+pub use style::{Color, FillRule};
+pub use transform::Transform;
+
+use cleanup::CleanupOptions;
+use occlusion::OcclusionOptions;
+
 const FLATTENING_TOLERANCE: f32 = 0.15;
 
 /// A CoordinatePair consists of an x and y coordinate.
@@ -59,6 +89,39 @@ impl convert::From<(f64, f64)> for CoordinatePair {
 /// A polyline is a vector of `CoordinatePair` instances.
 pub type Polyline = Vec<CoordinatePair>;
 
+/// The polylines of a single SVG element together with the style and
+/// grouping information they were drawn with.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Shape {
+    /// The `id` of the outermost `<g>` element containing this shape, if any.
+    pub layer: Option<String>,
+    /// The resolved stroke color. `None` if the stroke is `none` or unset.
+    pub stroke: Option<Color>,
+    /// The resolved fill color. `None` if the fill is `none`. Like in SVG,
+    /// parsed shapes without a fill are filled black.
+    pub fill: Option<Color>,
+    /// The resolved fill opacity, from 0 to 1.
+    pub fill_opacity: f64,
+    /// The resolved fill rule.
+    pub fill_rule: FillRule,
+    /// The polylines making up this shape.
+    pub polylines: Vec<Polyline>,
+}
+
+impl Default for Shape {
+    /// An empty shape without stroke and fill.
+    fn default() -> Self {
+        Shape {
+            layer: None,
+            stroke: None,
+            fill: None,
+            fill_opacity: 1.0,
+            fill_rule: FillRule::default(),
+            polylines: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct CurrentLine {
     /// The polyline containing the coordinate pairs for the current line.
@@ -146,7 +209,8 @@ impl CurrentLine {
 
 fn parse_path_token(data: &path::Token,
                     current_line: &mut CurrentLine,
-                    lines: &mut Vec<Polyline>) -> Result<(), String> {
+                    lines: &mut Vec<Polyline>,
+                    tolerance: f32) -> Result<(), String> {
     match data {
         &path::Token::MoveTo { abs, x, y } => {
             if current_line.is_valid() {
@@ -189,7 +253,7 @@ fn parse_path_token(data: &path::Token,
                     to: Point::new((current.x + x) as f32, (current.y + y) as f32),
                 }
             };
-            for point in curve.flattened(FLATTENING_TOLERANCE) {
+            for point in curve.flattened(tolerance) {
                 current_line.add_absolute(CoordinatePair::new(point.x as f64, point.y as f64));
             }
         },
@@ -209,7 +273,7 @@ fn parse_path_token(data: &path::Token,
                     to: Point::new((current.x + x) as f32, (current.y + y) as f32),
                 }
             };
-            for point in curve.flattened(FLATTENING_TOLERANCE) {
+            for point in curve.flattened(tolerance) {
                 current_line.add_absolute(CoordinatePair::new(point.x as f64, point.y as f64));
             }
         },
@@ -223,14 +287,14 @@ fn parse_path_token(data: &path::Token,
     Ok(())
 }
 
-fn parse_path(path: path::Tokenizer) -> Vec<Polyline> {
+fn parse_path(path: path::Tokenizer, tolerance: f32) -> Vec<Polyline> {
     debug!("New path");
 
     let mut lines = Vec::new();
 
     let mut line = CurrentLine::new();
     for token in path {
-        parse_path_token(&token, &mut line, &mut lines).unwrap();
+        parse_path_token(&token, &mut line, &mut lines, tolerance).unwrap();
     };
 
     // Path parsing is done, add previously parsing line if valid
@@ -241,26 +305,283 @@ fn parse_path(path: path::Tokenizer) -> Vec<Polyline> {
     lines
 }
 
-/// Parse an SVG string into a vector of polylines.
-pub fn parse(svg: &str) -> Vec<Polyline> {
-    // Tokenize the SVG strings into svg::Token instances
-    let tokenizer = Tokenizer::from_str(&svg);
-
-    // Loop over all tokens and parse the apths
-    tokenizer
-        .filter_map(|t| match t {
-            Ok(Token::Attribute(id, textframe)) => {
-                // Process only 'd' attributes
-                if id == svgparser::svg::Name::Svg(AttributeId::D) {
-                    let path = path::Tokenizer::from_span(textframe);
-                    Some(parse_path(path))
-                } else {
-                    None
+/// The style and grouping state of an element that is currently open.
+#[derive(Debug, Clone)]
+struct ElementState {
+    /// Whether this element is a `<g>`.
+    is_group: bool,
+    /// Whether this element is a `<clipPath>`.
+    is_clip_path: bool,
+    /// Number of `<g>` elements enclosing this element (including itself).
+    group_depth: usize,
+    layer: Option<String>,
+    stroke: Option<Color>,
+    fill: Option<Color>,
+    fill_opacity: f64,
+    fill_rule: FillRule,
+    /// The transform from the coordinates of this element to the coordinates
+    /// of the document, including the transforms of all enclosing elements.
+    transform: Transform,
+    /// The properties set by the `style` attribute, which take precedence
+    /// over the corresponding presentation attributes.
+    styled: Vec<String>,
+    /// The `id` of the enclosing `<clipPath>` element, if any.
+    clip_path_id: Option<String>,
+    /// The clip path referenced by this element.
+    clip_path: Option<String>,
+    /// The clip paths referenced by the enclosing elements, with the
+    /// transforms of the referencing elements.
+    ancestor_clip_paths: Vec<(String, Transform)>,
+    /// The path data of this element. It is only parsed once all attributes
+    /// are known, since curves are flattened depending on the transform.
+    path_data: Option<String>,
+}
+
+impl ElementState {
+    fn root(is_group: bool) -> Self {
+        ElementState {
+            is_group,
+            is_clip_path: false,
+            group_depth: if is_group { 1 } else { 0 },
+            layer: None,
+            stroke: None,
+            fill: Some(Color::new(0, 0, 0)),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::default(),
+            transform: Transform::identity(),
+            styled: Vec::new(),
+            clip_path_id: None,
+            clip_path: None,
+            ancestor_clip_paths: Vec::new(),
+            path_data: None,
+        }
+    }
+
+    /// Create the state of a child element, inheriting style and layer.
+    fn child(&self, is_group: bool) -> Self {
+        ElementState {
+            is_group,
+            is_clip_path: false,
+            group_depth: self.group_depth + if is_group { 1 } else { 0 },
+            layer: self.layer.clone(),
+            stroke: self.stroke,
+            fill: self.fill,
+            fill_opacity: self.fill_opacity,
+            fill_rule: self.fill_rule,
+            transform: self.transform,
+            styled: Vec::new(),
+            clip_path_id: self.clip_path_id.clone(),
+            clip_path: None,
+            ancestor_clip_paths: self.clip_paths(),
+            path_data: None,
+        }
+    }
+
+    fn apply_attribute(&mut self, id: AttributeId, value: StrSpan) {
+        match id {
+            AttributeId::D => self.path_data = Some(value.to_str().to_string()),
+            AttributeId::Id => {
+                if self.is_clip_path {
+                    self.clip_path_id = Some(value.to_str().to_string());
+                } else if self.is_group && self.group_depth == 1 {
+                    self.layer = Some(value.to_str().to_string());
+                }
+            },
+            AttributeId::Stroke => self.set_property("stroke", value.to_str(), false),
+            AttributeId::Fill => self.set_property("fill", value.to_str(), false),
+            AttributeId::FillOpacity => self.set_property("fill-opacity", value.to_str(), false),
+            AttributeId::FillRule => self.set_property("fill-rule", value.to_str(), false),
+            AttributeId::ClipPath => self.set_property("clip-path", value.to_str(), false),
+            AttributeId::Transform => {
+                match value.to_str().parse::<Transform>() {
+                    Ok(transform) => self.transform = self.transform * transform,
+                    Err(e) => warn!("{}", e),
+                }
+            },
+            AttributeId::Style => {
+                for (property, value) in style::style_declarations(value.to_str()) {
+                    self.set_property(property, value, true);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Set a style property, either from a presentation attribute or from a
+    /// declaration in the `style` attribute. Invalid values and values like
+    /// `inherit` keep the inherited value.
+    fn set_property(&mut self, property: &str, value: &str, from_style: bool) {
+        if !from_style && self.styled.iter().any(|p| p == property) {
+            return;
+        }
+        let applied = match property {
+            "stroke" => style::parse_paint(value).map(|paint| self.stroke = paint).is_some(),
+            "fill" => style::parse_paint(value).map(|paint| self.fill = paint).is_some(),
+            "fill-opacity" => style::parse_opacity(value).map(|opacity| self.fill_opacity = opacity).is_some(),
+            "fill-rule" => value.parse().map(|rule| self.fill_rule = rule).is_ok(),
+            "clip-path" => style::parse_clip_path(value).map(|clip_path| self.clip_path = clip_path).is_some(),
+            _ => false,
+        };
+        if applied && from_style {
+            self.styled.push(property.to_string());
+        }
+    }
+
+    /// Return the ids of all clip paths that apply to this element, with
+    /// the transforms of the elements referencing them. Like in SVG, clip
+    /// paths use the coordinates of the referencing element.
+    fn clip_paths(&self) -> Vec<(String, Transform)> {
+        let mut clip_paths = self.ancestor_clip_paths.clone();
+        clip_paths.extend(self.clip_path.clone().map(|id| (id, self.transform)));
+        clip_paths
+    }
+
+    /// Parse the path data of this element (if any) into a shape.
+    fn take_shape(&mut self) -> Option<Shape> {
+        let data = self.path_data.take()?;
+        // Curves are flattened before the transform is applied, so the
+        // tolerance is scaled to keep the deviation in the document the same
+        let t = &self.transform;
+        let scale = (t.a * t.d - t.b * t.c).abs().sqrt();
+        let tolerance = if scale > 0.0 && scale.is_finite() {
+            (f64::from(FLATTENING_TOLERANCE) / scale) as f32
+        } else {
+            FLATTENING_TOLERANCE
+        };
+        let polylines = parse_path(path::Tokenizer::from_span(StrSpan::from_str(&data)), tolerance);
+        Some(Shape {
+            layer: self.layer.clone(),
+            stroke: self.stroke,
+            fill: self.fill,
+            fill_opacity: self.fill_opacity,
+            fill_rule: self.fill_rule,
+            polylines: self.transform.apply_all(polylines),
+        })
+    }
+}
+
+/// Options for parsing.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ParseOptions {
+    /// If set, duplicate and collinear points as well as degenerate
+    /// polylines are removed from every parsed path.
+    pub cleanup: Option<CleanupOptions>,
+    /// Whether to orient the closed polylines of every parsed path
+    /// counter-clockwise for outlines and clockwise for holes, see
+    /// `nesting::normalize_orientation`.
+    pub normalize_orientation: bool,
+    /// If set, the parts of shapes hidden beneath the fills of later shapes
+    /// are removed, see `occlusion::remove_hidden`.
+    pub occlusion: Option<OcclusionOptions>,
+}
+
+/// Parse an SVG string into a vector of shapes.
+///
+/// Every element with path data results in one shape, in document order. The
+/// layer of a shape is the `id` of its outermost enclosing `<g>` element, as
+/// used for layers by editors like Inkscape.
+///
+/// Shapes inside of `<clipPath>` elements are not returned, but shapes that
+/// reference a clip path with the `clip-path` property are clipped to it.
+pub fn parse_shapes(svg: &str) -> Vec<Shape> {
+    parse_shapes_with_options(svg, &ParseOptions::default())
+}
+
+/// Parse an SVG string into a vector of shapes, see `parse_shapes`.
+pub fn parse_shapes_with_options(svg: &str, options: &ParseOptions) -> Vec<Shape> {
+    let mut stack: Vec<ElementState> = Vec::new();
+    // The shapes with the clip paths that apply to them
+    let mut clipped_shapes: Vec<(Shape, Vec<(String, Transform)>)> = Vec::new();
+    // The polylines of every clip path in its own coordinates, oriented so
+    // that their union is inside with the nonzero fill rule
+    let mut clip_paths: HashMap<String, Vec<Polyline>> = HashMap::new();
+
+    for token in Tokenizer::from_str(svg) {
+        match token {
+            Ok(Token::ElementStart(name)) => {
+                let is_group = name == Name::Svg(ElementId::G);
+                let mut state = match stack.last() {
+                    Some(parent) => parent.child(is_group),
+                    None => ElementState::root(is_group),
+                };
+                if name == Name::Svg(ElementId::ClipPath) {
+                    // The contents of a clip path are transformed along with
+                    // the elements referencing it, see `clip_paths`
+                    state.is_clip_path = true;
+                    state.clip_path_id = None;
+                    state.transform = Transform::identity();
+                }
+                stack.push(state);
+            },
+            Ok(Token::Attribute(Name::Svg(id), value)) => {
+                if let Some(state) = stack.last_mut() {
+                    state.apply_attribute(id, value);
+                }
+            },
+            Ok(Token::ElementEnd(ElementEnd::Close(_))) => {
+                stack.pop();
+            },
+            Ok(Token::ElementEnd(end)) => {
+                if let Some(state) = stack.last_mut() {
+                    if state.is_clip_path {
+                        if let Some(ref id) = state.clip_path_id {
+                            clip_paths.entry(id.clone()).or_default();
+                        }
+                    }
+                    if let Some(shape) = state.take_shape() {
+                        match state.clip_path_id {
+                            Some(ref id) => {
+                                let polylines = nesting::normalize_orientation(shape.polylines);
+                                clip_paths.entry(id.clone()).or_default().extend(polylines);
+                            },
+                            None => clipped_shapes.push((shape, state.clip_paths())),
+                        }
+                    }
+                }
+                if let ElementEnd::Empty = end {
+                    stack.pop();
                 }
             },
-            _ => None,
+            _ => {},
+        }
+    }
+
+    // Clip paths may be referenced before they are defined, so shapes are
+    // only clipped once the whole document has been parsed
+    let shapes = clipped_shapes.into_iter()
+        .map(|(mut shape, references)| {
+            for (id, transform) in references {
+                if let Some(clip) = clip_paths.get(&id) {
+                    let clip = transform.apply_all(clip.clone());
+                    shape.polylines = clip::clip_to_polygon(shape.polylines, &clip, FillRule::NonZero);
+                }
+            }
+            if let Some(ref cleanup_options) = options.cleanup {
+                shape.polylines = cleanup::cleanup(shape.polylines, cleanup_options);
+            }
+            if options.normalize_orientation {
+                shape.polylines = nesting::normalize_orientation(shape.polylines);
+            }
+            shape
         })
-        .flat_map(|v| v.into_iter())
+        .collect();
+
+    match options.occlusion {
+        Some(ref occlusion_options) => occlusion::remove_hidden(shapes, occlusion_options),
+        None => shapes,
+    }
+}
+
+/// Parse an SVG string into a vector of polylines.
+pub fn parse(svg: &str) -> Vec<Polyline> {
+    parse_with_options(svg, &ParseOptions::default())
+}
+
+/// Parse an SVG string into a vector of polylines, see `parse`.
+pub fn parse_with_options(svg: &str, options: &ParseOptions) -> Vec<Polyline> {
+    parse_shapes_with_options(svg, options)
+        .into_iter()
+        .flat_map(|shape| shape.polylines.into_iter())
         .collect()
 }
 
@@ -318,17 +639,17 @@ mod tests {
             abs: true,
             x: 1.0,
             y: 2.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         parse_path_token(&Token::LineTo {
             abs: true,
             x: 2.0,
             y: 3.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         parse_path_token(&Token::LineTo {
             abs: true,
             x: 3.0,
             y: 2.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         assert_eq!(lines.len(), 0);
         let finished = current_line.finish();
         assert_eq!(lines.len(), 0);
@@ -347,15 +668,15 @@ mod tests {
             abs: true,
             x: 1.0,
             y: 2.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         parse_path_token(&Token::HorizontalLineTo {
             abs: true,
             x: 3.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         parse_path_token(&Token::VerticalLineTo {
             abs: true,
             y: -1.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         assert_eq!(lines.len(), 0);
         let finished = current_line.finish();
         assert_eq!(lines.len(), 0);
@@ -374,12 +695,12 @@ mod tests {
             abs: true,
             x: 1.0,
             y: 2.0,
-        }, &mut current_line, &mut lines).unwrap();
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         let result = parse_path_token(&Token::SmoothQuadratic {
             abs: true,
             x: 3.0,
             y: 4.0,
-        }, &mut current_line, &mut lines);
+        }, &mut current_line, &mut lines, FLATTENING_TOLERANCE);
         assert!(result.is_err());
         assert_eq!(lines.len(), 0);
         let finished = current_line.finish();
@@ -392,13 +713,13 @@ mod tests {
     fn test_parse_segment_data_multiple() {
         let mut current_line = CurrentLine::new();
         let mut lines = Vec::new();
-        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 2.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 3.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 3.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 4.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 4.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 5.0, }, &mut current_line, &mut lines).unwrap();
-        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 5.0, }, &mut current_line, &mut lines).unwrap();
+        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 2.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 3.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 3.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 4.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 4.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::LineTo { abs: true, x: 2.0, y: 5.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
+        parse_path_token(&Token::MoveTo { abs: true, x: 1.0, y: 5.0, }, &mut current_line, &mut lines, FLATTENING_TOLERANCE).unwrap();
         assert_eq!(lines.len(), 3);
         assert_eq!(current_line.is_valid(), false);
         let finished = current_line.finish();
@@ -470,4 +791,211 @@ mod tests {
         assert_eq!(result[1][1], (0., 50.).into());
     }
 
+    #[test]
+    fn test_parse_shapes_layers_and_stroke() {
+        let input = r##"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <g id="layer1" stroke="#ff0000">
+                    <g id="nested">
+                        <path d="M 0,0 L 10,0" />
+                        <path d="M 0,0 L 0,10" style="fill:none;stroke:#0000ff" stroke="#00ff00" />
+                    </g>
+                </g>
+                <path d="M 5,5 L 10,10" />
+            </svg>
+        "##;
+        let shapes = parse_shapes(input);
+        assert_eq!(shapes.len(), 3);
+
+        assert_eq!(shapes[0].layer, Some("layer1".into()));
+        assert_eq!(shapes[0].stroke, Some(Color::new(255, 0, 0)));
+        assert_eq!(shapes[0].polylines, vec![vec![(0., 0.).into(), (10., 0.).into()]]);
+
+        assert_eq!(shapes[1].layer, Some("layer1".into()));
+        assert_eq!(shapes[1].stroke, Some(Color::new(0, 0, 255)));
+
+        assert_eq!(shapes[2].layer, None);
+        assert_eq!(shapes[2].stroke, None);
+        assert_eq!(shapes[2].polylines, vec![vec![(5., 5.).into(), (10., 10.).into()]]);
+    }
+
+    #[test]
+    fn test_parse_shapes_fill_rule() {
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <g fill-rule="evenodd">
+                    <path d="M 0,0 L 10,0"/>
+                    <path d="M 0,0 L 10,0" style="fill-rule:nonzero" fill-rule="evenodd"/>
+                    <path d="M 0,0 L 10,0" fill-rule="inherit"/>
+                </g>
+                <path d="M 0,0 L 10,0"/>
+            </svg>
+        "#;
+        let rules: Vec<FillRule> = parse_shapes(input).iter().map(|s| s.fill_rule).collect();
+        assert_eq!(rules, vec![FillRule::EvenOdd, FillRule::NonZero, FillRule::EvenOdd, FillRule::NonZero]);
+    }
+
+    #[test]
+    fn test_parse_shapes_fill() {
+        let input = r##"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M 0,0 L 10,0"/>
+                <g fill="#808080" fill-opacity="0.5">
+                    <path d="M 0,0 L 10,0"/>
+                    <path d="M 0,0 L 10,0" style="fill:none"/>
+                    <path d="M 0,0 L 10,0" style="fill-opacity:100%" fill="red"/>
+                </g>
+            </svg>
+        "##;
+        let fills: Vec<(Option<Color>, f64)> = parse_shapes(input).iter().map(|s| (s.fill, s.fill_opacity)).collect();
+        assert_eq!(fills, vec![
+            (Some(Color::new(0, 0, 0)), 1.0),
+            (Some(Color::new(128, 128, 128)), 0.5),
+            (None, 0.5),
+            (Some(Color::new(255, 0, 0)), 1.0),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_with_cleanup() {
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M 10,10 L 10,10 M 0,0 L 5,0 L 10,0 L 10,10 Z Z"/>
+            </svg>
+        "#;
+        assert_eq!(parse(input).len(), 2);
+        let options = ParseOptions { cleanup: Some(CleanupOptions::default()), ..ParseOptions::default() };
+        let result = parse_with_options(input, &options);
+        assert_eq!(result, vec![
+            vec![(0., 0.).into(), (10., 0.).into(), (10., 10.).into(), (0., 0.).into()],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_with_normalized_orientation() {
+        // Both squares run the same way, so the inner one is reversed
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M 0,0 L 10,0 L 10,10 L 0,10 Z M 2,2 L 8,2 L 8,8 L 2,8 Z"/>
+            </svg>
+        "#;
+        let options = ParseOptions { normalize_orientation: true, ..ParseOptions::default() };
+        let result = parse_with_options(input, &options);
+        assert_eq!(result[0], parse(input)[0]);
+        assert_eq!(result[1], vec![
+            (2., 2.).into(), (2., 8.).into(), (8., 8.).into(), (8., 2.).into(), (2., 2.).into(),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_with_occlusion() {
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M -5,5 L 15,5" fill="none" stroke="black"/>
+                <path d="M 0,0 L 10,0 L 10,10 L 0,10 Z" fill="white"/>
+            </svg>
+        "#;
+        let options = ParseOptions { occlusion: Some(OcclusionOptions::default()), ..ParseOptions::default() };
+        let result = parse_with_options(input, &options);
+        assert_eq!(result, vec![
+            vec![(-5., 5.).into(), (0., 5.).into()],
+            vec![(10., 5.).into(), (15., 5.).into()],
+            vec![(0., 0.).into(), (10., 0.).into(), (10., 10.).into(), (0., 10.).into(), (0., 0.).into()],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <g transform="translate(10, 20)">
+                    <path d="M 1,1 L 2,2" transform="scale(2)"/>
+                </g>
+                <path d="M 1,1 L 2,2"/>
+            </svg>
+        "#;
+        let result = parse(input);
+        assert_eq!(result, vec![
+            vec![(12., 22.).into(), (14., 24.).into()],
+            vec![(1., 1.).into(), (2., 2.).into()],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_with_clip_path() {
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <g clip-path="url(#page)">
+                    <path d="M -5,5 L 15,5"/>
+                    <path d="M 5,-5 L 5,15" style="clip-path: url(#half)"/>
+                </g>
+                <path d="M 20,20 L 30,30"/>
+                <defs>
+                    <clipPath id="page">
+                        <path d="M 0,0 L 10,0 L 10,10 L 0,10 Z"/>
+                    </clipPath>
+                    <clipPath id="half">
+                        <path d="M 0,0 L 10,0 L 10,2 L 0,2 Z"/>
+                        <path d="M 0,8 L 10,8 L 10,10 L 0,10 Z"/>
+                    </clipPath>
+                </defs>
+            </svg>
+        "#;
+        let result = parse(input);
+        assert_eq!(result, vec![
+            vec![(0., 5.).into(), (10., 5.).into()],
+            vec![(5., 0.).into(), (5., 2.).into()],
+            vec![(5., 8.).into(), (5., 10.).into()],
+            vec![(20., 20.).into(), (30., 30.).into()],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_with_transformed_clip_path() {
+        // Clip paths use the coordinates of the referencing element, not the
+        // ones of the clip path definition
+        let input = r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M 2,5 L 8,5" transform="translate(100,0)" clip-path="url(#c)"/>
+                <g transform="scale(2)" clip-path="url(#c)">
+                    <path d="M -5,1 L 15,1" transform="translate(0,1)"/>
+                </g>
+                <defs transform="translate(50,50)">
+                    <clipPath id="c">
+                        <path d="M 0,0 L 10,0 L 10,10 L 0,10 Z"/>
+                    </clipPath>
+                </defs>
+            </svg>
+        "#;
+        let result = parse(input);
+        assert_eq!(result, vec![
+            vec![(102., 5.).into(), (108., 5.).into()],
+            vec![(0., 4.).into(), (20., 4.).into()],
+        ]);
+    }
+
+    #[test]
+    fn test_flattening_tolerance_follows_transform() {
+        // A scaled up curve is flattened with more points, so that it
+        // deviates from the true curve by the same amount in the result
+        let svg = |transform: &str| format!(r#"
+            <?xml version="1.0" encoding="UTF-8" standalone="no"?>
+            <svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+                <path d="M 0,0 C 0,10 10,10 10,0" transform="{}"/>
+            </svg>
+        "#, transform);
+        let small = parse(&svg("scale(1)"));
+        let large = parse(&svg("scale(10)"));
+        assert!(large[0].len() > small[0].len());
+    }
+
 }
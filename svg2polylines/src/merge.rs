@@ -0,0 +1,229 @@
+//! Join polylines whose end points coincide, to save pen lifts.
+//!
+//! SVG exports often split one continuous stroke into many subpaths. This
+//! pass chains polylines whose end points lie within a tolerance of each
+//! other into a single polyline, reversing polylines where needed.
+//!
+//! Optionally, closed polylines are rotated so that they start at a vertex
+//! that touches the end of another polyline, so that they can be drawn
+//! without lifting the pen as well.
+//!
+//! The drawn geometry does not change, except that gaps up to the tolerance
+//! between joined end points are bridged by a straight segment.
+
+use {CoordinatePair, Polyline};
+use util::{is_closed, rotate_closed, PointGrid};
+
+/// Options for joining polylines.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeOptions {
+    /// The maximum distance between two end points that are joined.
+    pub tolerance: f64,
+    /// Whether polylines may be reversed in order to join them.
+    pub reverse: bool,
+    /// Whether closed polylines may be rotated to start at the end point of
+    /// another polyline.
+    pub rotate_closed: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            tolerance: 1e-3,
+            reverse: true,
+            rotate_closed: true,
+        }
+    }
+}
+
+/// Append `next` to `chain`, dropping the first point of `next` if it is the
+/// same as the last point of `chain`.
+fn append(chain: &mut Polyline, next: Polyline) {
+    let skip = if chain.last() == next.first() { 1 } else { 0 };
+    chain.extend(next.into_iter().skip(skip));
+}
+
+/// The state of the merge pass: the remaining polylines and indices over
+/// their end points and (for rotation) the vertices of closed polylines.
+struct Merger {
+    lines: Vec<Option<Polyline>>,
+    /// End points: polyline index and whether it is the last point.
+    endpoints: PointGrid<(usize, bool)>,
+    /// Vertices of closed polylines: polyline index and vertex index.
+    vertices: PointGrid<(usize, usize)>,
+    options: MergeOptions,
+}
+
+impl Merger {
+    fn new(polylines: Vec<Polyline>, options: &MergeOptions) -> Self {
+        let mut merger = Merger {
+            lines: Vec::with_capacity(polylines.len()),
+            endpoints: PointGrid::new(options.tolerance),
+            vertices: PointGrid::new(options.tolerance),
+            options: options.clone(),
+        };
+        for (i, polyline) in polylines.into_iter().enumerate() {
+            if !polyline.is_empty() {
+                merger.endpoints.insert(polyline[0], (i, false));
+                merger.endpoints.insert(polyline[polyline.len() - 1], (i, true));
+                if options.rotate_closed && is_closed(&polyline) {
+                    for (k, p) in polyline[1..polyline.len() - 1].iter().enumerate() {
+                        merger.vertices.insert(*p, (i, k + 1));
+                    }
+                }
+            }
+            merger.lines.push(Some(polyline));
+        }
+        merger
+    }
+
+    /// Remove polyline `i` from the pass and the indices.
+    fn take(&mut self, i: usize) -> Polyline {
+        let polyline = self.lines[i].take().expect("Polyline used twice");
+        if !polyline.is_empty() {
+            self.endpoints.remove(&polyline[0], (i, false));
+            self.endpoints.remove(&polyline[polyline.len() - 1], (i, true));
+            if self.options.rotate_closed && is_closed(&polyline) {
+                for (k, p) in polyline[1..polyline.len() - 1].iter().enumerate() {
+                    self.vertices.remove(p, (i, k + 1));
+                }
+            }
+        }
+        polyline
+    }
+
+    /// Find and remove a polyline that can continue at `p`. If `forward` is
+    /// true, the returned polyline starts near `p`, otherwise it ends there.
+    fn continuation(&mut self, p: &CoordinatePair, forward: bool) -> Option<Polyline> {
+        let tolerance = self.options.tolerance;
+        let reverse = self.options.reverse;
+        let found = self.endpoints.nearest_within(p, tolerance, |(_, is_end)| reverse || is_end != forward);
+        if let Some((i, is_end)) = found {
+            let mut polyline = self.take(i);
+            if is_end == forward {
+                polyline.reverse();
+            }
+            return Some(polyline);
+        }
+        if self.options.rotate_closed {
+            if let Some((i, k)) = self.vertices.nearest_within(p, tolerance, |_| true) {
+                let polyline = self.take(i);
+                return Some(rotate_closed(&polyline, k));
+            }
+        }
+        None
+    }
+
+    fn run(mut self) -> Vec<Polyline> {
+        let mut merged = Vec::new();
+        for seed in 0..self.lines.len() {
+            if self.lines[seed].is_none() {
+                continue;
+            }
+            let mut chain = self.take(seed);
+            if chain.is_empty() {
+                continue;
+            }
+            if self.options.rotate_closed && is_closed(&chain) {
+                let tolerance = self.options.tolerance;
+                let touching = (1..chain.len() - 1)
+                    .find(|&k| self.endpoints.nearest_within(&chain[k], tolerance, |_| true).is_some());
+                if let Some(k) = touching {
+                    chain = rotate_closed(&chain, k);
+                }
+            }
+            while let Some(next) = self.continuation(&chain[chain.len() - 1], true) {
+                append(&mut chain, next);
+            }
+            while let Some(mut previous) = self.continuation(&chain[0], false) {
+                append(&mut previous, chain);
+                chain = previous;
+            }
+            merged.push(chain);
+        }
+        merged
+    }
+}
+
+/// Join polylines whose end points are within the tolerance of each other.
+///
+/// The merged polylines are returned in the order of the first polyline that
+/// they contain. Empty polylines are removed.
+pub fn merge(polylines: Vec<Polyline>, options: &MergeOptions) -> Vec<Polyline> {
+    Merger::new(polylines, options).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_merge_split_stroke() {
+        let polylines = vec![
+            polyline(&[(1.0, 0.0), (2.0, 0.0)]),
+            polyline(&[(5.0, 5.0), (6.0, 6.0)]),
+            polyline(&[(3.0, 0.0), (2.0, 0.0)]),
+            polyline(&[(0.0, 0.0), (1.0, 0.0)]),
+        ];
+        let merged = merge(polylines, &MergeOptions::default());
+        assert_eq!(merged, vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]),
+            polyline(&[(5.0, 5.0), (6.0, 6.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_merge_without_reversing() {
+        let polylines = vec![
+            polyline(&[(1.0, 0.0), (2.0, 0.0)]),
+            polyline(&[(3.0, 0.0), (2.0, 0.0)]),
+            polyline(&[(0.0, 0.0), (1.0, 0.0)]),
+        ];
+        let options = MergeOptions { reverse: false, ..MergeOptions::default() };
+        let merged = merge(polylines, &options);
+        assert_eq!(merged, vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)]),
+            polyline(&[(3.0, 0.0), (2.0, 0.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_merge_tolerance() {
+        let polylines = vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0)]),
+            polyline(&[(1.0005, 0.0), (2.0, 0.0)]),
+            polyline(&[(2.1, 0.0), (3.0, 0.0)]),
+        ];
+        let merged = merge(polylines, &MergeOptions::default());
+        assert_eq!(merged, vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0005, 0.0), (2.0, 0.0)]),
+            polyline(&[(2.1, 0.0), (3.0, 0.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_merge_rotate_closed() {
+        let square = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+        let tail = polyline(&[(20.0, 10.0), (10.0, 10.0)]);
+        let merged = merge(vec![square.clone(), tail.clone()], &MergeOptions::default());
+        assert_eq!(merged, vec![
+            polyline(&[
+                (10.0, 10.0), (0.0, 10.0), (0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (20.0, 10.0),
+            ]),
+        ]);
+
+        let options = MergeOptions { rotate_closed: false, ..MergeOptions::default() };
+        assert_eq!(merge(vec![square.clone(), tail.clone()], &options), vec![square, tail]);
+    }
+
+    #[test]
+    fn test_rotate_closed() {
+        let triangle = polyline(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)]);
+        assert_eq!(rotate_closed(&triangle, 2), polyline(&[(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]));
+        assert_eq!(rotate_closed(&triangle, 0), triangle);
+    }
+}
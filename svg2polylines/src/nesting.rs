@@ -0,0 +1,221 @@
+//! Containment hierarchy of closed polylines.
+//!
+//! Closed polylines that lie inside another closed polyline are holes of it,
+//! or islands within such a hole, and so on. This module finds the enclosing
+//! polyline of every closed polyline, which is needed to cut holes before
+//! their outline or to orient outlines and holes consistently.
+//!
+//! Like in the rest of the crate, orientations refer to a coordinate system
+//! with the y axis pointing up. In SVG, where the y axis points down, they
+//! appear mirrored.
+
+use {CoordinatePair, Polyline};
+use util::{bounds, is_closed, segment_distance, signed_area, winding, Segment};
+
+/// The direction in which a closed polyline runs around its inside.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+}
+
+/// A closed polyline in the containment hierarchy.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Node {
+    /// The index of the polyline in the input.
+    pub index: usize,
+    /// The node of the innermost polyline enclosing this one.
+    pub parent: Option<usize>,
+    /// The nodes of the polylines directly enclosed by this one.
+    pub children: Vec<usize>,
+    /// The number of polylines enclosing this one.
+    pub depth: usize,
+    /// The (unsigned) enclosed area.
+    pub area: f64,
+    pub orientation: Orientation,
+}
+
+impl Node {
+    /// Whether the polyline is a hole, i.e. enclosed by an odd number of
+    /// polylines.
+    pub fn is_hole(&self) -> bool {
+        self.depth % 2 == 1
+    }
+}
+
+/// Whether the closed polyline `inner` lies inside the closed polyline
+/// `outer`. Polylines are assumed not to cross, so the first point of `inner`
+/// that is not on `outer` decides.
+fn encloses(outer: &[Segment], inner: &[CoordinatePair], epsilon: f64) -> bool {
+    inner.iter()
+        .find(|p| outer.iter().all(|&(a, b)| segment_distance(p, &a, &b) > epsilon))
+        .is_some_and(|p| winding(outer.iter(), p) != 0)
+}
+
+/// Build the containment hierarchy of the closed polylines.
+///
+/// Returns one node per closed polyline, in input order. Open polylines are
+/// ignored. Parent and child references are indices into the returned
+/// vector. Polylines are assumed not to cross each other; identical
+/// polylines don't enclose each other.
+pub fn nesting(polylines: &[Polyline]) -> Vec<Node> {
+    let closed: Vec<usize> = (0..polylines.len())
+        .filter(|&i| polylines[i].len() > 3 && is_closed(&polylines[i]))
+        .collect();
+    let areas: Vec<f64> = closed.iter().map(|&i| signed_area(&polylines[i])).collect();
+    let boxes: Vec<_> = closed.iter().map(|&i| bounds(polylines[i].iter()).unwrap()).collect();
+    let edges: Vec<Vec<Segment>> = closed.iter()
+        .map(|&i| polylines[i].windows(2).map(|w| (w[0], w[1])).collect())
+        .collect();
+    let epsilon = boxes.iter()
+        .map(|&(min, max)| (max.x - min.x).max(max.y - min.y))
+        .fold(0.0, f64::max) * 1e-9;
+
+    // Larger polylines can only be enclosed by even larger ones, so handling
+    // them first makes their depth known when their children are found
+    let mut by_area: Vec<usize> = (0..closed.len()).collect();
+    by_area.sort_by(|&a, &b| areas[b].abs().total_cmp(&areas[a].abs()));
+
+    let mut nodes: Vec<Node> = closed.iter().zip(&areas)
+        .map(|(&index, &area)| Node {
+            index,
+            parent: None,
+            children: Vec::new(),
+            depth: 0,
+            area: area.abs(),
+            orientation: if area < 0.0 { Orientation::Clockwise } else { Orientation::CounterClockwise },
+        })
+        .collect();
+    for (position, &node) in by_area.iter().enumerate() {
+        let (min, max) = boxes[node];
+        let parent = by_area[..position].iter().rev().cloned()
+            .filter(|&candidate| nodes[candidate].area > nodes[node].area)
+            .find(|&candidate| {
+                let (outer_min, outer_max) = boxes[candidate];
+                outer_min.x <= min.x && outer_min.y <= min.y && outer_max.x >= max.x && outer_max.y >= max.y
+                    && encloses(&edges[candidate], &polylines[nodes[node].index], epsilon)
+            });
+        if let Some(parent) = parent {
+            nodes[node].parent = Some(parent);
+            nodes[node].depth = nodes[parent].depth + 1;
+        }
+    }
+    for node in 0..nodes.len() {
+        if let Some(parent) = nodes[node].parent {
+            nodes[parent].children.push(node);
+        }
+    }
+    nodes
+}
+
+/// Return the number of closed polylines enclosing every polyline.
+///
+/// Unlike `nesting`, this includes open polylines, e.g. engravings inside a
+/// part. For closed polylines, this is their depth in the hierarchy.
+pub fn depths(polylines: &[Polyline]) -> Vec<usize> {
+    let nodes = nesting(polylines);
+    let mut depths = vec![0; polylines.len()];
+    let mut is_node = vec![false; polylines.len()];
+    for node in &nodes {
+        depths[node.index] = node.depth;
+        is_node[node.index] = true;
+    }
+
+    let outlines: Vec<_> = nodes.iter()
+        .map(|node| {
+            let polyline = &polylines[node.index];
+            (bounds(polyline.iter()).unwrap(), polyline.windows(2).map(|w| (w[0], w[1])).collect::<Vec<Segment>>())
+        })
+        .collect();
+    let epsilon = bounds(polylines.iter().flat_map(|p| p.iter()))
+        .map_or(0.0, |(min, max)| (max.x - min.x).max(max.y - min.y) * 1e-9);
+    for (i, polyline) in polylines.iter().enumerate() {
+        if is_node[i] || polyline.is_empty() {
+            continue;
+        }
+        let (min, max) = bounds(polyline.iter()).unwrap();
+        depths[i] = outlines.iter()
+            .filter(|&&((outer_min, outer_max), ref edges)| {
+                outer_min.x <= min.x && outer_min.y <= min.y && outer_max.x >= max.x && outer_max.y >= max.y
+                    && encloses(edges, polyline, epsilon)
+            })
+            .count();
+    }
+    depths
+}
+
+/// Orient closed polylines so that outlines run counter-clockwise and holes
+/// clockwise, according to their containment hierarchy.
+///
+/// With this orientation, the nonzero and evenodd fill rules agree. Open
+/// polylines are left unchanged.
+pub fn normalize_orientation(mut polylines: Vec<Polyline>) -> Vec<Polyline> {
+    for node in nesting(&polylines) {
+        let expected = if node.is_hole() { Orientation::Clockwise } else { Orientation::CounterClockwise };
+        if node.area > 0.0 && node.orientation != expected {
+            polylines[node.index].reverse();
+        }
+    }
+    polylines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(min: f64, max: f64) -> Polyline {
+        vec![(min, min).into(), (max, min).into(), (max, max).into(), (min, max).into(), (min, min).into()]
+    }
+
+    #[test]
+    fn test_nesting() {
+        let polylines = vec![
+            square(2.0, 8.0),
+            vec![(0.0, 0.0).into(), (1.0, 1.0).into()],
+            square(0.0, 10.0),
+            square(4.0, 6.0),
+            square(20.0, 30.0),
+            square(0.0, 1.0),
+        ];
+        let nodes = nesting(&polylines);
+        let summary: Vec<_> = nodes.iter().map(|n| (n.index, n.parent, n.depth)).collect();
+        assert_eq!(summary, vec![
+            (0, Some(1), 1),
+            (2, None, 0),
+            (3, Some(0), 2),
+            (4, None, 0),
+            (5, Some(1), 1),
+        ]);
+        assert_eq!(nodes[1].children, vec![0, 4]);
+        assert!(nodes[0].is_hole() && !nodes[2].is_hole());
+        assert_eq!(nodes[2].area, 4.0);
+        assert_eq!(nodes[2].orientation, Orientation::CounterClockwise);
+    }
+
+    #[test]
+    fn test_depths() {
+        let polylines = vec![
+            vec![(5.0, 5.0).into(), (5.5, 5.5).into()],
+            square(0.0, 10.0),
+            square(4.0, 6.0),
+            vec![(1.0, 1.0).into(), (2.0, 1.0).into()],
+            vec![(1.0, 1.0).into(), (20.0, 1.0).into()],
+            vec![],
+        ];
+        assert_eq!(depths(&polylines), vec![2, 0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_normalize_orientation() {
+        let mut outer = square(0.0, 10.0);
+        outer.reverse();
+        let polylines = normalize_orientation(vec![outer, square(2.0, 8.0), square(4.0, 6.0)]);
+        let areas: Vec<f64> = polylines.iter().map(|p| signed_area(p)).collect();
+        assert_eq!(areas, vec![100.0, -36.0, 4.0]);
+        assert_eq!(polylines[1], {
+            let mut hole = square(2.0, 8.0);
+            hole.reverse();
+            hole
+        });
+    }
+}
@@ -0,0 +1,142 @@
+//! Hidden-line removal using filled shapes as occluders.
+//!
+//! In SVG, shapes are painted in document order, so the fill of a shape
+//! covers everything drawn before it. A plotter draws all lines regardless,
+//! which makes layered illustrations look like wireframes. Removing the parts
+//! of every shape that lie beneath the opaque fills of later shapes restores
+//! the intended look.
+
+use std::mem;
+
+use {CoordinatePair, FillRule, Polyline, Shape};
+use clip::remove_inside;
+use util::bounds;
+
+/// Options for hidden-line removal.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OcclusionOptions {
+    /// The minimal fill opacity of a shape to hide what lies beneath it.
+    pub min_opacity: f64,
+}
+
+impl Default for OcclusionOptions {
+    fn default() -> Self {
+        OcclusionOptions {
+            min_opacity: 1.0,
+        }
+    }
+}
+
+/// Return whether two bounding boxes overlap.
+fn overlaps(a: &(CoordinatePair, CoordinatePair), b: &(CoordinatePair, CoordinatePair)) -> bool {
+    a.0.x <= b.1.x && b.0.x <= a.1.x && a.0.y <= b.1.y && b.0.y <= a.1.y
+}
+
+/// An occluder: the index of the shape, its polylines, fill rule and
+/// bounding box.
+type Occluder = (usize, Vec<Polyline>, FillRule, (CoordinatePair, CoordinatePair));
+
+/// Remove the parts of every shape that are hidden beneath the fills of
+/// later shapes, in document order.
+///
+/// Shapes with a fill (and a fill opacity of at least `min_opacity`) hide
+/// everything inside of them according to their fill rule, but not their own
+/// polylines. Polylines are split where they disappear beneath a fill, so
+/// closed polylines may become open.
+pub fn remove_hidden(mut shapes: Vec<Shape>, options: &OcclusionOptions) -> Vec<Shape> {
+    // The fills cover the original outlines of the occluders, so they are
+    // taken before any occluder is clipped by later ones
+    let occluders: Vec<Occluder> = shapes.iter()
+        .enumerate()
+        .filter(|&(_, shape)| shape.fill.is_some() && shape.fill_opacity >= options.min_opacity)
+        .filter_map(|(i, shape)| {
+            bounds(shape.polylines.iter().flat_map(|p| p.iter()))
+                .map(|b| (i, shape.polylines.clone(), shape.fill_rule, b))
+        })
+        .collect();
+    for (i, outline, fill_rule, occluder_bounds) in occluders {
+        for shape in shapes[..i].iter_mut() {
+            let visible = bounds(shape.polylines.iter().flat_map(|p| p.iter()))
+                .is_some_and(|b| overlaps(&b, &occluder_bounds));
+            if visible {
+                let polylines = mem::take(&mut shape.polylines);
+                shape.polylines = remove_inside(polylines, &outline, fill_rule);
+            }
+        }
+    }
+    shapes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Color;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polyline {
+        polyline(&[(x, y), (x + size, y), (x + size, y + size), (x, y + size), (x, y)])
+    }
+
+    fn filled(polylines: Vec<Polyline>, fill_opacity: f64) -> Shape {
+        Shape { fill: Some(Color::new(255, 255, 255)), fill_opacity, polylines, ..Shape::default() }
+    }
+
+    #[test]
+    fn test_remove_hidden() {
+        let shapes = vec![
+            Shape { polylines: vec![polyline(&[(-5.0, 3.0), (20.0, 3.0)])], ..Shape::default() },
+            filled(vec![square(0.0, 0.0, 10.0)], 1.0),
+            filled(vec![square(5.0, 5.0, 10.0)], 1.0),
+        ];
+        let result = remove_hidden(shapes, &OcclusionOptions::default());
+        assert_eq!(result[0].polylines, vec![
+            polyline(&[(-5.0, 3.0), (0.0, 3.0)]),
+            polyline(&[(10.0, 3.0), (20.0, 3.0)]),
+        ]);
+        // Only the part of the first square beneath the second one is hidden
+        assert_eq!(result[1].polylines, vec![
+            polyline(&[(5.0, 10.0), (0.0, 10.0), (0.0, 0.0), (10.0, 0.0), (10.0, 5.0)]),
+        ]);
+        assert_eq!(result[2].polylines, vec![square(5.0, 5.0, 10.0)]);
+    }
+
+    #[test]
+    fn test_occluders_keep_their_outline() {
+        // A non-convex shape whose notch reaches into an earlier fill: the
+        // earlier fill still hides the line there, even though the later
+        // shape hides part of the earlier outline
+        let c_shape = polyline(&[
+            (8.0, 0.0), (12.0, 0.0), (12.0, 10.0), (8.0, 10.0), (8.0, 7.0), (11.0, 7.0), (11.0, 3.0), (8.0, 3.0),
+            (8.0, 0.0),
+        ]);
+        let shapes = vec![
+            Shape { polylines: vec![polyline(&[(-5.0, 5.0), (20.0, 5.0)])], ..Shape::default() },
+            filled(vec![square(0.0, 0.0, 10.0)], 1.0),
+            filled(vec![c_shape], 1.0),
+        ];
+        let result = remove_hidden(shapes, &OcclusionOptions::default());
+        assert_eq!(result[0].polylines, vec![
+            polyline(&[(-5.0, 5.0), (0.0, 5.0)]),
+            polyline(&[(10.0, 5.0), (11.0, 5.0)]),
+            polyline(&[(12.0, 5.0), (20.0, 5.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_transparent_fills() {
+        let line = polyline(&[(-5.0, 5.0), (15.0, 5.0)]);
+        let shapes = vec![
+            Shape { polylines: vec![line.clone()], ..Shape::default() },
+            filled(vec![square(0.0, 0.0, 10.0)], 0.5),
+            Shape { polylines: vec![square(0.0, 0.0, 10.0)], ..Shape::default() },
+        ];
+        let result = remove_hidden(shapes, &OcclusionOptions::default());
+        assert_eq!(result[0].polylines, vec![line]);
+        let options = OcclusionOptions { min_opacity: 0.5 };
+        let result = remove_hidden(result, &options);
+        assert_eq!(result[0].polylines.len(), 2);
+    }
+}
@@ -0,0 +1,487 @@
+//! Offsetting of the outlines of filled regions, e.g. to compensate for the
+//! radius of a router bit or the kerf of a laser.
+//!
+//! The region enclosed by a set of polylines (according to a fill rule) is
+//! grown or shrunk by moving its outline by a distance. Every edge of the
+//! outline is moved first, and the gaps between moved edges are closed with
+//! miter, round or square joins. The result (the raw offset) intersects
+//! itself wherever parts of the outline come closer than twice the distance,
+//! so it is split at all intersections and only the pieces bounding the
+//! region it winds around are kept. This handles holes, shapes splitting
+//! into several islands and islands merging.
+//!
+//! Open polylines can be turned into the outline of their stroke, which is
+//! offset the same way.
+
+use {CoordinatePair, FillRule, Polyline, Shape};
+use util::{bounds, distance, intersection, is_closed, PointGrid, Segment, SegmentGrid, WindingIndex};
+
+/// Return the size of the bounding box of the segments, used to scale
+/// numerical tolerances.
+fn scale(segments: &[Segment]) -> f64 {
+    bounds(segments.iter().flat_map(|s| vec![&s.0, &s.1]))
+        .map_or(1.0, |(min, max)| (max.x - min.x).max(max.y - min.y).max(1e-9))
+}
+
+/// Split segments at all their mutual intersections.
+fn split_segments(segments: &[Segment]) -> Vec<Segment> {
+    let total_length: f64 = segments.iter().map(|s| distance(&s.0, &s.1)).sum();
+    let mut grid = SegmentGrid::new(total_length / segments.len().max(1) as f64);
+    for s in segments {
+        grid.insert(s.0, s.1);
+    }
+
+    let mut splits: Vec<Vec<(f64, CoordinatePair)>> = vec![Vec::new(); segments.len()];
+    for (i, &(a, b)) in segments.iter().enumerate() {
+        for j in grid.near(&a, &b).into_iter().filter(|&j| j > i) {
+            let (c, d) = segments[j];
+            let (t, u) = match intersection(&a, &b, &c, &d) {
+                Some((t, u)) if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) => (t, u),
+                _ => continue,
+            };
+            // Use existing end points where possible, so that the pieces of
+            // both segments share exactly the same points
+            let point = if t == 0.0 {
+                a
+            } else if t == 1.0 {
+                b
+            } else if u == 0.0 {
+                c
+            } else if u == 1.0 {
+                d
+            } else {
+                CoordinatePair::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+            };
+            if t > 0.0 && t < 1.0 {
+                splits[i].push((t, point));
+            }
+            if u > 0.0 && u < 1.0 {
+                splits[j].push((u, point));
+            }
+        }
+    }
+
+    let mut pieces = Vec::with_capacity(segments.len());
+    for (&(a, b), mut points) in segments.iter().zip(splits) {
+        points.sort_by(|x, y| x.0.total_cmp(&y.0));
+        let mut start = a;
+        for (_, point) in points.into_iter().chain(Some((1.0, b))) {
+            if point != start {
+                pieces.push((start, point));
+                start = point;
+            }
+        }
+    }
+    pieces
+}
+
+/// Join directed segments into polylines, following shared end points.
+fn chain(segments: &[Segment], epsilon: f64) -> Vec<Polyline> {
+    let mut starts = PointGrid::new(epsilon);
+    for (i, s) in segments.iter().enumerate() {
+        starts.insert(s.0, i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut polylines = Vec::new();
+    for i in 0..segments.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        starts.remove(&segments[i].0, i);
+        let mut polyline = vec![segments[i].0, segments[i].1];
+        while let Some(j) = starts.nearest_within(&polyline[polyline.len() - 1], epsilon, |_| true) {
+            used[j] = true;
+            starts.remove(&segments[j].0, j);
+            polyline.push(segments[j].1);
+            if distance(&polyline[0], &segments[j].1) <= epsilon {
+                let last = polyline.len() - 1;
+                polyline[last] = polyline[0];
+                break;
+            }
+        }
+        polylines.push(polyline);
+    }
+    polylines
+}
+
+/// Return the closed boundary of the region where the winding number of the
+/// closed outlines formed by the segments satisfies `inside`, oriented so
+/// that the region is on the left of every edge.
+fn boundary<F: Fn(i32) -> bool>(segments: &[Segment], inside: F) -> Vec<Polyline> {
+    let epsilon = scale(segments) * 1e-9;
+    let index = WindingIndex::new(segments);
+
+    // Keep the pieces of segments with the region on exactly one side
+    let pieces: Vec<Segment> = split_segments(segments).into_iter()
+        .filter_map(|(a, b)| {
+            let length = distance(&a, &b);
+            let (nx, ny) = (-(b.y - a.y) / length * epsilon * 100.0, (b.x - a.x) / length * epsilon * 100.0);
+            let mid = CoordinatePair::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+            let inside_at = |x: f64, y: f64| inside(index.winding(&CoordinatePair::new(x, y)));
+            match (inside_at(mid.x + nx, mid.y + ny), inside_at(mid.x - nx, mid.y - ny)) {
+                (true, false) => Some((a, b)),
+                (false, true) => Some((b, a)),
+                _ => None,
+            }
+        })
+        .collect();
+    chain(&pieces, epsilon)
+}
+
+/// Return the closed outline of the region enclosed by the polylines,
+/// oriented so that the region is on the left of every edge (i.e. outer
+/// outlines are counter-clockwise and holes clockwise, in a coordinate system
+/// with the y axis pointing up).
+///
+/// Like in SVG, open polylines are implicitly closed. Edges inside the region
+/// (e.g. of overlapping polylines) are removed.
+pub(crate) fn outline(polylines: &[Polyline], fill_rule: FillRule) -> Vec<Polyline> {
+    let edges: Vec<Segment> = polylines.iter()
+        .filter(|p| p.len() > 2)
+        .flat_map(|p| (0..p.len()).map(move |i| (p[i], p[(i + 1) % p.len()])))
+        .filter(|&(a, b)| a != b)
+        .collect();
+    boundary(&edges, |w| fill_rule.is_inside(w))
+}
+
+/// How the moved edges are joined on the outer side of a turn.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Join {
+    /// Extend the edges until they meet, up to the miter limit.
+    Miter,
+    /// Connect the edges with an arc around the vertex.
+    #[default]
+    Round,
+    /// Cut the corner off at the offset distance from the vertex.
+    Square,
+}
+
+/// Options for offsetting.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OffsetOptions {
+    /// The join on the outer side of turns. In stroke outlines, this is also
+    /// used for the caps at the ends of open polylines.
+    pub join: Join,
+    /// The maximum distance of a miter join from its vertex, as a multiple of
+    /// the offset distance. Longer miters are beveled.
+    pub miter_limit: f64,
+    /// The maximum deviation of round joins from a true arc.
+    pub tolerance: f64,
+}
+
+impl Default for OffsetOptions {
+    fn default() -> Self {
+        OffsetOptions {
+            join: Join::Round,
+            miter_limit: 2.0,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Append the points of a round join around `center`, from `start` to `end`,
+/// turning by `sweep` radians.
+fn round_join(points: &mut Vec<CoordinatePair>, center: &CoordinatePair, start: CoordinatePair, end: CoordinatePair, sweep: f64, tolerance: f64) {
+    let radius = distance(center, &start);
+    let max_step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+    let steps = (sweep.abs() / max_step.max(1e-3)).ceil().max(1.0) as usize;
+    let start_angle = (start.y - center.y).atan2(start.x - center.x);
+    points.push(start);
+    for k in 1..steps {
+        let angle = start_angle + sweep * k as f64 / steps as f64;
+        points.push(CoordinatePair::new(center.x + radius * angle.cos(), center.y + radius * angle.sin()));
+    }
+    points.push(end);
+}
+
+/// Return the raw offset of a closed polyline, with edges moved to the left
+/// by `shift` (to the right if negative).
+fn raw_offset(ring: &[CoordinatePair], shift: f64, options: &OffsetOptions) -> Vec<CoordinatePair> {
+    let vertices = &ring[..ring.len() - 1];
+    let n = vertices.len();
+    let direction = |i: usize| {
+        let (a, b) = (vertices[i % n], vertices[(i + 1) % n]);
+        let length = distance(&a, &b);
+        ((b.x - a.x) / length, (b.y - a.y) / length)
+    };
+    let radius = shift.abs();
+    let mut points = Vec::new();
+    for (i, v) in vertices.iter().enumerate() {
+        let (incoming, outgoing) = (direction(i + n - 1), direction(i));
+        let start = CoordinatePair::new(v.x - incoming.1 * shift, v.y + incoming.0 * shift);
+        let end = CoordinatePair::new(v.x - outgoing.1 * shift, v.y + outgoing.0 * shift);
+        let cross = incoming.0 * outgoing.1 - incoming.1 * outgoing.0;
+        let dot = incoming.0 * outgoing.0 + incoming.1 * outgoing.1;
+        let miter = |scale: f64| CoordinatePair::new(
+            v.x - (incoming.1 + outgoing.1) * scale,
+            v.y + (incoming.0 + outgoing.0) * scale,
+        );
+        // The moved edges leave a gap on the outer side of a turn
+        if cross * shift < 0.0 || (cross == 0.0 && dot < 0.0) {
+            match options.join {
+                Join::Round => {
+                    let sweep = -shift.signum() * cross.atan2(dot).abs();
+                    round_join(&mut points, v, start, end, sweep, options.tolerance);
+                },
+                Join::Miter if dot > -1.0 && (2.0 / (1.0 + dot)).sqrt() <= options.miter_limit => {
+                    points.push(miter(shift / (1.0 + dot)));
+                },
+                Join::Miter => {
+                    points.push(start);
+                    points.push(end);
+                },
+                Join::Square => {
+                    // Cut perpendicular to the bisector of the edge normals,
+                    // or to the edge where it turns back on itself
+                    let normal = miter(shift);
+                    let (mx, my) = if dot > -1.0 {
+                        let length = distance(v, &normal);
+                        ((normal.x - v.x) / length, (normal.y - v.y) / length)
+                    } else {
+                        incoming
+                    };
+                    let along_in = radius * (1.0 - (-incoming.1 * mx + incoming.0 * my) * shift.signum()) / (incoming.0 * mx + incoming.1 * my);
+                    let along_out = radius * (1.0 - (-outgoing.1 * mx + outgoing.0 * my) * shift.signum()) / -(outgoing.0 * mx + outgoing.1 * my);
+                    points.push(CoordinatePair::new(start.x + incoming.0 * along_in, start.y + incoming.1 * along_in));
+                    points.push(CoordinatePair::new(end.x - outgoing.0 * along_out, end.y - outgoing.1 * along_out));
+                },
+            }
+        } else if dot > 0.0 {
+            // The moved edges overlap on the inner side of a turn, where they
+            // are joined at their intersection
+            points.push(miter(shift / (1.0 + dot)));
+        } else {
+            // For sharp turns, the intersection is far away. Going through
+            // the vertex instead keeps the part cut off between the moved
+            // edges from winding around the offset region.
+            points.push(start);
+            points.push(*v);
+            points.push(end);
+        }
+    }
+    points
+}
+
+/// Offset closed polylines to the right by `distance`, and return the
+/// outline of the region the result winds around.
+fn offset_rings<'a, I: IntoIterator<Item=&'a Polyline>>(rings: I, distance: f64, options: &OffsetOptions) -> Vec<Polyline> {
+    let mut raw = Vec::new();
+    for ring in rings {
+        let points = raw_offset(ring, -distance, options);
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            if *a != b {
+                raw.push((*a, b));
+            }
+        }
+    }
+
+    // The raw offset winds around the offset region the same way as the
+    // rings wind around the region on their left, while the parts cut off by
+    // self-intersections wind the other way. Counter-clockwise rings have a
+    // negative winding number on their inside.
+    boundary(&raw, |w| w < 0).into_iter()
+        .filter(|p| is_closed(p) && p.len() > 3)
+        .collect()
+}
+
+/// Offset an outline (as returned by `outline`) by `distance`, outwards for
+/// positive and inwards for negative distances.
+pub(crate) fn offset_outline(outline: &[Polyline], distance: f64, options: &OffsetOptions) -> Vec<Polyline> {
+    let outline = outline.iter().filter(|p| is_closed(p));
+    if distance == 0.0 {
+        return outline.cloned().collect();
+    }
+    offset_rings(outline, distance, options)
+}
+
+/// Offset the region enclosed by the polylines by `distance`.
+///
+/// Positive distances grow the region, i.e. outer outlines move outwards and
+/// holes shrink, negative distances shrink it. All polylines together form a
+/// single region according to the fill rule, so that holes are handled and
+/// overlapping parts are merged. The result consists of closed polylines,
+/// with outer outlines counter-clockwise and holes clockwise (in a
+/// coordinate system with the y axis pointing up).
+pub fn offset(polylines: &[Polyline], fill_rule: FillRule, distance: f64, options: &OffsetOptions) -> Vec<Polyline> {
+    offset_outline(&outline(polylines, fill_rule), distance, options)
+}
+
+/// Offset the region filled by a shape by `distance`, using its fill rule.
+pub fn offset_shape(shape: &Shape, distance: f64, options: &OffsetOptions) -> Vec<Polyline> {
+    offset(&shape.polylines, shape.fill_rule, distance, options)
+}
+
+/// Return the outline of the area within `distance` of the polylines, i.e.
+/// of the polylines stroked with a width of twice the distance.
+///
+/// Open polylines get caps matching the join. Closed polylines result in an
+/// outer and an inner outline.
+pub fn stroke_outline(polylines: &[Polyline], distance: f64, options: &OffsetOptions) -> Vec<Polyline> {
+    if distance <= 0.0 {
+        return Vec::new();
+    }
+    // Every polyline is traced forth and back, so that moving the edges to
+    // the right covers both of its sides. Closed polylines are traced in
+    // both directions separately, to avoid caps where they are closed.
+    let mut rings: Vec<Polyline> = Vec::new();
+    for polyline in polylines {
+        let mut points: Polyline = Vec::with_capacity(polyline.len());
+        for p in polyline {
+            if points.last() != Some(p) {
+                points.push(*p);
+            }
+        }
+        if points.len() < 2 {
+            continue;
+        }
+        if is_closed(&points) {
+            // Unless it only goes back and forth anyway
+            if points.len() > 3 {
+                rings.push(points.iter().rev().cloned().collect());
+            }
+        } else {
+            let back: Polyline = points[..points.len() - 1].iter().rev().cloned().collect();
+            points.extend(back);
+        }
+        rings.push(points);
+    }
+    offset_rings(&rings, distance, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::signed_area;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    fn square(min: f64, max: f64) -> Polyline {
+        polyline(&[(min, min), (max, min), (max, max), (min, max), (min, min)])
+    }
+
+    fn round(tolerance: f64) -> OffsetOptions {
+        OffsetOptions { tolerance, ..OffsetOptions::default() }
+    }
+
+    fn join(join: Join, miter_limit: f64) -> OffsetOptions {
+        OffsetOptions { join, miter_limit, ..OffsetOptions::default() }
+    }
+
+    #[test]
+    fn test_outline_orientation_and_union() {
+        // Clockwise square becomes counter-clockwise
+        let mut clockwise = square(0.0, 10.0);
+        clockwise.reverse();
+        let result = outline(&[clockwise], FillRule::NonZero);
+        assert_eq!(result.len(), 1);
+        assert!((signed_area(&result[0]) - 100.0).abs() < 1e-9);
+
+        // Overlapping squares are joined
+        let result = outline(&[square(0.0, 10.0), square(5.0, 15.0)], FillRule::NonZero);
+        assert_eq!(result.len(), 1);
+        assert!((signed_area(&result[0]) - 175.0).abs() < 1e-9);
+
+        // With evenodd, the overlap is a hole
+        let result = outline(&[square(0.0, 10.0), square(5.0, 15.0)], FillRule::EvenOdd);
+        let total: f64 = result.iter().map(|p| signed_area(p)).sum();
+        assert!((total - 150.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_offset_square() {
+        let outline = outline(&[square(0.0, 10.0)], FillRule::NonZero);
+
+        let inset = offset_outline(&outline, -2.0, &round(0.01));
+        assert_eq!(inset.len(), 1);
+        assert!((signed_area(&inset[0]) - 36.0).abs() < 1e-9);
+        for p in &inset[0] {
+            assert!(p.x >= 2.0 - 1e-9 && p.x <= 8.0 + 1e-9 && p.y >= 2.0 - 1e-9 && p.y <= 8.0 + 1e-9);
+        }
+
+        // Outwards, the corners are rounded
+        let outset = offset_outline(&outline, 1.0, &round(0.001));
+        assert_eq!(outset.len(), 1);
+        let expected = 100.0 + 4.0 * 10.0 + ::std::f64::consts::PI;
+        assert!((signed_area(&outset[0]) - expected).abs() < 0.01);
+
+        assert!(offset_outline(&outline, -5.5, &round(0.01)).is_empty());
+    }
+
+    #[test]
+    fn test_offset_splits_and_holes() {
+        // Two squares joined by a narrow bridge split into two islands
+        let dumbbell = polyline(&[
+            (0.0, 0.0), (10.0, 0.0), (10.0, 4.5), (20.0, 4.5), (20.0, 0.0), (30.0, 0.0),
+            (30.0, 10.0), (20.0, 10.0), (20.0, 5.5), (10.0, 5.5), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0),
+        ]);
+        let inset = offset_outline(&outline(&[dumbbell], FillRule::NonZero), -1.0, &round(0.01));
+        assert_eq!(inset.len(), 2);
+        for ring in &inset {
+            // Slightly bulging towards the former bridge
+            let area = signed_area(ring);
+            assert!(area > 64.0 && area < 64.2, "{}", area);
+        }
+
+        // A hole grows when the region shrinks
+        let mut hole = square(4.0, 6.0);
+        hole.reverse();
+        let inset = offset_outline(&outline(&[square(0.0, 10.0), hole], FillRule::NonZero), -1.0, &round(0.001));
+        assert_eq!(inset.len(), 2);
+        let areas: Vec<f64> = inset.iter().map(|p| signed_area(p)).collect();
+        assert!(areas.iter().any(|&a| (a - 64.0).abs() < 1e-6));
+        let hole_area = 4.0 + 4.0 * 2.0 + ::std::f64::consts::PI;
+        assert!(areas.iter().any(|&a| (a + hole_area).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_offset_joins() {
+        let square = [square(0.0, 10.0)];
+        let area = |join: Join, miter_limit: f64| {
+            let result = offset(&square, FillRule::NonZero, 1.0, &self::join(join, miter_limit));
+            assert_eq!(result.len(), 1);
+            signed_area(&result[0])
+        };
+        assert!((area(Join::Miter, 2.0) - 144.0).abs() < 1e-9);
+        // The miter length of a right angle is sqrt(2)
+        assert!((area(Join::Miter, 1.2) - (144.0 - 4.0 * 0.5)).abs() < 1e-9);
+        let corner = (2.0_f64.sqrt() - 1.0).powi(2);
+        assert!((area(Join::Square, 2.0) - (144.0 - 4.0 * corner)).abs() < 1e-9);
+
+        // Inwards, the joins are on the inside of the holes
+        let mut hole = self::square(4.0, 6.0);
+        hole.reverse();
+        let result = offset(&[self::square(0.0, 10.0), hole], FillRule::NonZero, -1.0, &self::join(Join::Miter, 2.0));
+        let areas: Vec<f64> = result.iter().map(|p| signed_area(p)).collect();
+        assert!(areas.iter().any(|&a| (a - 64.0).abs() < 1e-9));
+        assert!(areas.iter().any(|&a| (a + 16.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_stroke_outline() {
+        let line = [polyline(&[(0.0, 0.0), (10.0, 0.0)])];
+        let result = stroke_outline(&line, 1.0, &round(0.001));
+        assert_eq!(result.len(), 1);
+        assert!((signed_area(&result[0]) - (20.0 + ::std::f64::consts::PI)).abs() < 0.01);
+        let result = stroke_outline(&line, 1.0, &join(Join::Square, 2.0));
+        assert!((signed_area(&result[0]) - 24.0).abs() < 1e-9);
+        let result = stroke_outline(&line, 1.0, &join(Join::Miter, 2.0));
+        assert!((signed_area(&result[0]) - 20.0).abs() < 1e-9);
+
+        // A closed polyline has an outer and an inner outline
+        let result = stroke_outline(&[square(0.0, 10.0)], 1.0, &join(Join::Miter, 2.0));
+        let mut areas: Vec<f64> = result.iter().map(|p| signed_area(p)).collect();
+        areas.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(areas.len(), 2);
+        assert!((areas[0] + 64.0).abs() < 1e-9 && (areas[1] - 144.0).abs() < 1e-9);
+
+        // Crossing strokes are merged
+        let cross = [polyline(&[(0.0, 5.0), (10.0, 5.0)]), polyline(&[(5.0, 0.0), (5.0, 10.0)])];
+        let result = stroke_outline(&cross, 1.0, &join(Join::Miter, 2.0));
+        assert_eq!(result.len(), 1);
+        assert!((signed_area(&result[0]) - (20.0 + 20.0 - 4.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,331 @@
+//! Reduce pen-up travel by reordering and reversing polylines.
+//!
+//! The polylines are first ordered with a greedy nearest neighbour search
+//! (using a grid based spatial index on the polyline end points), which is
+//! then improved with 2-opt until no further improvement is found or the
+//! time budget is exhausted.
+//!
+//! The drawn geometry does not change, only the order in which polylines are
+//! drawn and the direction in which they are drawn.
+//!
+//! For cutting, the order can be restricted so that everything inside a
+//! closed polyline is cut before the polyline itself. Otherwise the part may
+//! shift or fall out before its holes are cut.
+
+use std::time::{Duration, Instant};
+
+use {CoordinatePair, Polyline};
+use nesting::depths;
+use util::{bounds, distance, PointGrid};
+
+/// Options for the travel optimization.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OptimizeOptions {
+    /// The position of the pen before the first polyline.
+    pub start: CoordinatePair,
+    /// Whether polylines may be drawn in reverse direction. This must be
+    /// disabled if the drawing direction matters (e.g. for some cutting
+    /// tools). The 2-opt improvement is only done if reversing is allowed.
+    pub reverse: bool,
+    /// Maximum time spent on the 2-opt improvement. `None` means no limit.
+    pub time_budget: Option<Duration>,
+    /// Whether to draw the polylines enclosed by a closed polyline (holes
+    /// and inner features) before it. Polylines are drawn by nesting depth,
+    /// from the innermost to the outermost, and travel is minimized within
+    /// every depth.
+    pub inside_out: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            start: CoordinatePair::new(0.0, 0.0),
+            reverse: true,
+            time_budget: Some(Duration::from_secs(1)),
+            inside_out: false,
+        }
+    }
+}
+
+/// Statistics about an optimization run.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct OptimizeReport {
+    /// The pen-up travel distance in the original order.
+    pub travel_before: f64,
+    /// The pen-up travel distance in the optimized order.
+    pub travel_after: f64,
+}
+
+/// Return the total pen-up travel distance needed to draw the polylines in
+/// order, starting at `start`.
+pub fn travel_distance(polylines: &[Polyline], start: CoordinatePair) -> f64 {
+    let mut position = start;
+    let mut total = 0.0;
+    for polyline in polylines.iter().filter(|p| !p.is_empty()) {
+        total += distance(&position, &polyline[0]);
+        position = polyline[polyline.len() - 1];
+    }
+    total
+}
+
+/// A polyline end point in the spatial index: the polyline index and whether
+/// the point is the end (instead of the start) of the polyline.
+type Endpoint = (usize, bool);
+
+/// Build a grid over the polyline end points for nearest neighbour queries.
+fn spatial_index(points: &[(CoordinatePair, Endpoint)]) -> PointGrid<Endpoint> {
+    let (min, max) = bounds(points.iter().map(|(p, _)| p))
+        .unwrap_or((CoordinatePair::new(0.0, 0.0), CoordinatePair::new(0.0, 0.0)));
+    // Aim for roughly one end point per cell, but never use more cells along
+    // one axis than there are points (e.g. if all points are on one line)
+    let n = points.len().max(1) as f64;
+    let (width, height) = (max.x - min.x, max.y - min.y);
+    let cell_size = (width * height / n).sqrt().max(width.max(height) / n);
+    let mut index = PointGrid::new(cell_size);
+    for &(p, endpoint) in points {
+        index.insert(p, endpoint);
+    }
+    index
+}
+
+/// Order the polylines greedily, always continuing with the polyline whose
+/// start (or end, if reversing is allowed) is closest to the current pen
+/// position. Returns `(polyline index, reversed)` pairs.
+fn nearest_neighbour(polylines: &[Polyline], start: CoordinatePair, reverse: bool) -> Vec<Endpoint> {
+    let mut points = Vec::with_capacity(polylines.len() * 2);
+    for (i, polyline) in polylines.iter().enumerate() {
+        points.push((polyline[0], (i, false)));
+        if reverse {
+            points.push((polyline[polyline.len() - 1], (i, true)));
+        }
+    }
+    let mut index = spatial_index(&points);
+
+    let mut order = Vec::with_capacity(polylines.len());
+    let mut position = start;
+    while let Some((i, reversed)) = index.nearest(&position) {
+        let polyline = &polylines[i];
+        let (first, last) = (polyline[0], polyline[polyline.len() - 1]);
+        index.remove(&first, (i, false));
+        if reverse {
+            index.remove(&last, (i, true));
+        }
+        position = if reversed { first } else { last };
+        order.push((i, reversed));
+    }
+    order
+}
+
+/// Improve the order with 2-opt moves: reversing a run of consecutive
+/// polylines (including the direction of each polyline in it) leaves the
+/// travel moves inside the run unchanged, so only the two travel moves at its
+/// boundaries need to be compared.
+fn two_opt(polylines: &[Polyline], order: &mut [Endpoint], start: CoordinatePair, deadline: Option<Instant>) {
+    let first = |&(i, reversed): &Endpoint| {
+        let p = &polylines[i];
+        if reversed { p[p.len() - 1] } else { p[0] }
+    };
+    let last = |&(i, reversed): &Endpoint| {
+        let p = &polylines[i];
+        if reversed { p[0] } else { p[p.len() - 1] }
+    };
+
+    let n = order.len();
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..n {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return;
+            }
+            let before = if i == 0 { start } else { last(&order[i - 1]) };
+            for j in i..n {
+                let after = if j + 1 < n { Some(first(&order[j + 1])) } else { None };
+                let old = distance(&before, &first(&order[i]))
+                    + after.map_or(0.0, |a| distance(&last(&order[j]), &a));
+                let new = distance(&before, &last(&order[j]))
+                    + after.map_or(0.0, |a| distance(&first(&order[i]), &a));
+                if new < old - 1e-9 {
+                    order[i..j + 1].reverse();
+                    for entry in &mut order[i..j + 1] {
+                        entry.1 = !entry.1;
+                    }
+                    improved = true;
+                }
+            }
+        }
+    }
+}
+
+/// Group the polylines by the number of closed polylines enclosing them,
+/// from the innermost to the outermost.
+fn inside_out_levels(polylines: Vec<Polyline>) -> Vec<Vec<Polyline>> {
+    let depths = depths(&polylines);
+    let mut levels: Vec<Vec<Polyline>> = vec![Vec::new(); depths.iter().max().map_or(0, |&d| d + 1)];
+    for (polyline, depth) in polylines.into_iter().zip(depths) {
+        levels[depth].push(polyline);
+    }
+    levels.reverse();
+    levels.retain(|level| !level.is_empty());
+    levels
+}
+
+/// Reorder (and, if allowed, reverse) the polylines to minimize the pen-up
+/// travel distance. Empty polylines are removed.
+pub fn optimize(polylines: Vec<Polyline>, options: &OptimizeOptions) -> (Vec<Polyline>, OptimizeReport) {
+    let polylines: Vec<Polyline> = polylines.into_iter().filter(|p| !p.is_empty()).collect();
+    let travel_before = travel_distance(&polylines, options.start);
+    let deadline = options.time_budget.map(|budget| Instant::now() + budget);
+
+    let mut optimized: Vec<Polyline> = Vec::with_capacity(polylines.len());
+    let levels = if options.inside_out { inside_out_levels(polylines) } else { vec![polylines] };
+    for polylines in levels {
+        let start = optimized.last().map_or(options.start, |p| p[p.len() - 1]);
+        let mut order = nearest_neighbour(&polylines, start, options.reverse);
+        if options.reverse {
+            two_opt(&polylines, &mut order, start, deadline);
+        }
+
+        let mut slots: Vec<Option<Polyline>> = polylines.into_iter().map(Some).collect();
+        optimized.extend(order.into_iter().map(|(i, reversed)| {
+            let mut polyline = slots[i].take().expect("Polyline used twice");
+            if reversed {
+                polyline.reverse();
+            }
+            polyline
+        }));
+    }
+
+    let travel_after = travel_distance(&optimized, options.start);
+    (optimized, OptimizeReport { travel_before, travel_after })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Polyline {
+        vec![(x1, y1).into(), (x2, y2).into()]
+    }
+
+    #[test]
+    fn test_travel_distance() {
+        let polylines = vec![line(0.0, 3.0, 0.0, 10.0), vec![], line(0.0, 10.0, 5.0, 10.0)];
+        assert_eq!(travel_distance(&polylines, (4.0, 0.0).into()), 5.0);
+        assert_eq!(travel_distance(&[], (4.0, 0.0).into()), 0.0);
+    }
+
+    #[test]
+    fn test_optimize_reorders_and_reverses() {
+        let polylines = vec![
+            line(20.0, 0.0, 30.0, 0.0),
+            line(10.0, 0.0, 0.0, 0.0),
+            line(20.0, 0.0, 10.0, 0.0),
+        ];
+        let (optimized, report) = optimize(polylines, &OptimizeOptions::default());
+        assert_eq!(optimized, vec![
+            line(0.0, 0.0, 10.0, 0.0),
+            line(10.0, 0.0, 20.0, 0.0),
+            line(20.0, 0.0, 30.0, 0.0),
+        ]);
+        assert_eq!(report.travel_before, 20.0 + 20.0 + 20.0);
+        assert_eq!(report.travel_after, 0.0);
+    }
+
+    #[test]
+    fn test_optimize_without_reversing() {
+        let polylines = vec![line(10.0, 0.0, 0.0, 0.0), line(1.0, 1.0, 2.0, 2.0)];
+        let options = OptimizeOptions { reverse: false, ..OptimizeOptions::default() };
+        let (optimized, _) = optimize(polylines, &options);
+        assert_eq!(optimized, vec![line(1.0, 1.0, 2.0, 2.0), line(10.0, 0.0, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_optimize_start_point() {
+        let polylines = vec![line(0.0, 0.0, 0.0, 1.0), line(100.0, 0.0, 100.0, 1.0)];
+        let options = OptimizeOptions { start: (100.0, 0.0).into(), ..OptimizeOptions::default() };
+        let (optimized, _) = optimize(polylines, &options);
+        assert_eq!(optimized[0], line(100.0, 0.0, 100.0, 1.0));
+    }
+
+    #[test]
+    fn test_two_opt_improves_greedy() {
+        // Greedy zig-zags around the start and then has to come all the way
+        // back, 2-opt finishes one side first
+        let polylines = vec![
+            line(1.0, 0.0, 1.1, 0.0),
+            line(-1.2, 0.0, -1.1, 0.0),
+            line(3.5, 0.0, 3.6, 0.0),
+            line(-5.0, 0.0, -4.9, 0.0),
+        ];
+        let start = CoordinatePair::new(0.0, 0.0);
+        let greedy = nearest_neighbour(&polylines, start, true);
+        let mut improved = greedy.clone();
+        two_opt(&polylines, &mut improved, start, None);
+
+        let cost = |order: &[Endpoint]| {
+            let lines: Vec<Polyline> = order.iter().map(|&(i, r)| {
+                let mut p = polylines[i].clone();
+                if r { p.reverse(); }
+                p
+            }).collect();
+            travel_distance(&lines, start)
+        };
+        assert!(cost(&improved) < cost(&greedy));
+    }
+
+    #[test]
+    fn test_optimize_many() {
+        // A grid of short strokes in scrambled order
+        let mut polylines = Vec::new();
+        for i in 0..400 {
+            let k = (i * 7919) % 400;
+            let (x, y) = ((k % 20) as f64 * 5.0, (k / 20) as f64 * 5.0);
+            polylines.push(line(x, y, x + 1.0, y));
+        }
+        let (optimized, report) = optimize(polylines, &OptimizeOptions::default());
+        assert_eq!(optimized.len(), 400);
+        assert!(report.travel_after < report.travel_before / 5.0);
+    }
+
+    #[test]
+    fn test_optimize_coinciding_end_points() {
+        // All end points coincide, so the grid cells are tiny, and the start
+        // is far away from them
+        let polylines = vec![
+            vec![(5.0, 5.0).into(), (6.0, 6.0).into(), (5.0, 5.0).into()],
+            vec![(5.0, 5.0).into(), (4.0, 6.0).into(), (5.0, 5.0).into()],
+        ];
+        let options = OptimizeOptions { start: (1000.0, -1000.0).into(), ..OptimizeOptions::default() };
+        let (optimized, _) = optimize(polylines, &options);
+        assert_eq!(optimized.len(), 2);
+    }
+
+    #[test]
+    fn test_optimize_inside_out() {
+        let square = |min: f64, max: f64| -> Polyline {
+            vec![(min, min).into(), (max, min).into(), (max, max).into(), (min, max).into(), (min, min).into()]
+        };
+        let polylines = vec![
+            square(0.0, 10.0),
+            square(20.0, 30.0),
+            square(2.0, 4.0),
+            line(6.0, 6.0, 8.0, 8.0),
+            square(22.0, 28.0),
+            square(24.0, 26.0),
+        ];
+        let options = OptimizeOptions { inside_out: true, ..OptimizeOptions::default() };
+        let (optimized, _) = optimize(polylines.clone(), &options);
+        let position = |p: &Polyline| optimized.iter().position(|q| {
+            let mut reversed = q.clone();
+            reversed.reverse();
+            q == p || &reversed == p
+        }).unwrap();
+        // Everything inside a closed polyline comes before it
+        for &(inner, outer) in &[(2, 0), (3, 0), (4, 1), (5, 4)] {
+            assert!(position(&polylines[inner]) < position(&polylines[outer]));
+        }
+        // The innermost square is on its own level
+        assert_eq!(position(&polylines[5]), 0);
+    }
+}
@@ -0,0 +1,158 @@
+//! Remove segments that are drawn more than once.
+//!
+//! When adjacent shapes share an edge (tiles, maps, CAD exports), the shared
+//! edge is contained in both outlines and gets drawn twice. This pass keeps
+//! the first occurrence of every segment and removes the portions of later
+//! segments that are collinear with and overlap an already drawn segment,
+//! splitting polylines where needed.
+
+use {CoordinatePair, Polyline};
+use util::{distance, is_closed, SegmentGrid};
+
+/// Return the intervals (as distances from `a`) of the segment from `a` to
+/// `b` that are not covered by any segment in the grid.
+fn uncovered(grid: &SegmentGrid, a: &CoordinatePair, b: &CoordinatePair, tolerance: f64) -> Vec<(f64, f64)> {
+    let length = distance(a, b);
+    let (dx, dy) = ((b.x - a.x) / length, (b.y - a.y) / length);
+    let offset = |p: &CoordinatePair| ((p.x - a.x) * dy - (p.y - a.y) * dx).abs();
+    let position = |p: &CoordinatePair| (p.x - a.x) * dx + (p.y - a.y) * dy;
+
+    // A drawn segment covers a part of this segment if both of its end points
+    // lie within the tolerance of the line through this segment
+    let mut covered: Vec<(f64, f64)> = grid.near(a, b).into_iter()
+        .map(|i| grid.segment(i))
+        .filter(|&(c, d)| offset(&c) <= tolerance && offset(&d) <= tolerance)
+        .map(|(c, d)| {
+            let (tc, td) = (position(&c), position(&d));
+            (tc.min(td).max(0.0), tc.max(td).min(length))
+        })
+        .filter(|&(start, end)| end > start)
+        .collect();
+    covered.sort_by(|x, y| x.0.total_cmp(&y.0).then(x.1.total_cmp(&y.1)));
+
+    let mut pieces = Vec::new();
+    let mut position = 0.0;
+    for (start, end) in covered {
+        if start > position {
+            pieces.push((position, start));
+        }
+        position = end.max(position);
+    }
+    if position < length {
+        pieces.push((position, length));
+    }
+
+    // Drop slivers left over next to covered parts
+    pieces.retain(|&(start, end)| end - start > tolerance || (start == 0.0 && end == length));
+    pieces
+}
+
+/// Remove the portions of segments that overlap an earlier segment within
+/// `tolerance`.
+///
+/// Polylines are processed in order, so the first occurrence of an edge is
+/// kept. Polylines are split where parts are removed, but the order of the
+/// remaining parts is preserved. Closed polylines that lose a part are
+/// rotated so that the rest is drawn as a single polyline where possible.
+pub fn remove_overlaps(polylines: Vec<Polyline>, tolerance: f64) -> Vec<Polyline> {
+    let segment_count: usize = polylines.iter().map(|p| p.len().saturating_sub(1)).sum();
+    let total_length: f64 = polylines.iter()
+        .flat_map(|p| p.windows(2))
+        .map(|w| distance(&w[0], &w[1]))
+        .sum();
+    let mut grid = SegmentGrid::new((total_length / segment_count.max(1) as f64).max(tolerance));
+
+    let mut result = Vec::with_capacity(polylines.len());
+    for polyline in &polylines {
+        let mut chains: Vec<Polyline> = Vec::new();
+        // Whether the last chain ends at the current point
+        let mut connected = false;
+        for w in polyline.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let length = distance(&a, &b);
+            if length == 0.0 {
+                continue;
+            }
+            let point_at = |t: f64| if t == length {
+                b
+            } else {
+                CoordinatePair::new(a.x + (b.x - a.x) * t / length, a.y + (b.y - a.y) * t / length)
+            };
+            let pieces = uncovered(&grid, &a, &b, tolerance);
+            for &(start, end) in &pieces {
+                match chains.last_mut() {
+                    Some(chain) if connected && start == 0.0 => chain.push(point_at(end)),
+                    _ => chains.push(vec![point_at(start), point_at(end)]),
+                }
+                connected = end == length;
+            }
+            if pieces.is_empty() {
+                connected = false;
+            }
+            grid.insert(a, b);
+        }
+
+        // A closed polyline with a gap is drawn from the end of the gap
+        // around to its start
+        if is_closed(polyline) && connected && chains.len() > 1 && chains[0][0] == polyline[0] {
+            let first = chains.remove(0);
+            chains.last_mut().unwrap().extend(first.into_iter().skip(1));
+        }
+        result.extend(chains);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_shared_edge() {
+        let left = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+        let right = polyline(&[(10.0, 0.0), (20.0, 0.0), (20.0, 10.0), (10.0, 10.0), (10.0, 0.0)]);
+        let result = remove_overlaps(vec![left.clone(), right], 1e-3);
+        assert_eq!(result, vec![
+            left,
+            polyline(&[(10.0, 0.0), (20.0, 0.0), (20.0, 10.0), (10.0, 10.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_partial_overlap() {
+        let result = remove_overlaps(vec![
+            polyline(&[(0.0, 0.0), (10.0, 0.0)]),
+            polyline(&[(0.0, 5.0), (5.0, 0.0005), (15.0, 0.0)]),
+        ], 1e-3);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[1], polyline(&[(0.0, 5.0), (5.0, 0.0005)]));
+        assert!((result[2][0].x - 10.0).abs() < 1e-6);
+        assert_eq!(result[2][1], (15.0, 0.0).into());
+    }
+
+    #[test]
+    fn test_retrace_and_crossing() {
+        // Going back along the same line is removed, crossing lines are kept
+        let result = remove_overlaps(vec![
+            polyline(&[(0.0, 0.0), (10.0, 0.0), (5.0, 0.0)]),
+            polyline(&[(5.0, -5.0), (5.0, 5.0)]),
+        ], 1e-3);
+        assert_eq!(result, vec![
+            polyline(&[(0.0, 0.0), (10.0, 0.0)]),
+            polyline(&[(5.0, -5.0), (5.0, 5.0)]),
+        ]);
+    }
+
+    #[test]
+    fn test_closed_polyline_rotated() {
+        let result = remove_overlaps(vec![
+            polyline(&[(0.0, 10.0), (10.0, 10.0)]),
+            polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]),
+        ], 1e-3);
+        assert_eq!(result[1], polyline(&[(0.0, 10.0), (0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]));
+    }
+}
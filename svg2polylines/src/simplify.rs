@@ -0,0 +1,246 @@
+//! Reduce the number of points of polylines.
+//!
+//! Flattened curves and traced artwork often contain far more points than
+//! needed for drawing. Both algorithms guarantee that no point of the
+//! original polyline is further than the tolerance away from the simplified
+//! polyline. The first and last point are always kept, so closed polylines
+//! stay closed.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use {CoordinatePair, Polyline};
+use util::{distance, is_closed, segment_distance};
+
+/// The simplification algorithm.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Algorithm {
+    /// Ramer–Douglas–Peucker: recursively keep the point furthest away from
+    /// the line between the end points.
+    RamerDouglasPeucker,
+    /// Visvalingam–Whyatt: repeatedly remove the point forming the smallest
+    /// triangle with its neighbours, as long as the maximum deviation is not
+    /// exceeded. Tends to give smoother results for curves.
+    Visvalingam,
+}
+
+/// Statistics about a simplification run.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SimplifyStats {
+    /// The number of points before simplification.
+    pub points_before: usize,
+    /// The number of points after simplification.
+    pub points_after: usize,
+}
+
+/// Mark the points kept by Ramer–Douglas–Peucker.
+fn rdp(points: &[CoordinatePair], tolerance: f64, keep: &mut [bool]) {
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let furthest = (first + 1..last)
+            .map(|i| (i, segment_distance(&points[i], &points[first], &points[last])))
+            .fold(None, |acc: Option<(usize, f64)>, (i, d)| match acc {
+                Some((_, best)) if best >= d => acc,
+                _ => Some((i, d)),
+            });
+        if let Some((i, d)) = furthest {
+            if d > tolerance {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+    }
+}
+
+/// A removal candidate for Visvalingam–Whyatt.
+#[derive(PartialEq)]
+struct Candidate {
+    area: f64,
+    index: usize,
+    /// The neighbours at the time the area was computed.
+    prev: usize,
+    next: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    /// Reversed ordering by area, so that `BinaryHeap` is a min-heap.
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        other.area.total_cmp(&self.area)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn triangle_area(a: &CoordinatePair, b: &CoordinatePair, c: &CoordinatePair) -> f64 {
+    ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2.0
+}
+
+/// Mark the points kept by Visvalingam–Whyatt, never removing a point if
+/// that would move any original point further than `tolerance` away.
+fn visvalingam(points: &[CoordinatePair], tolerance: f64, keep: &mut [bool]) {
+    let n = points.len();
+    let mut prev: Vec<usize> = (0..n).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1).min(n - 1)).collect();
+    let candidate = |i: usize, prev: usize, next: usize| Candidate {
+        area: triangle_area(&points[prev], &points[i], &points[next]),
+        index: i,
+        prev,
+        next,
+    };
+
+    let mut heap: BinaryHeap<Candidate> = (1..n - 1).map(|i| candidate(i, i - 1, i + 1)).collect();
+    while let Some(c) = heap.pop() {
+        // Skip outdated candidates whose neighbours have changed
+        if !keep[c.index] || prev[c.index] != c.prev || next[c.index] != c.next {
+            continue;
+        }
+        let within_tolerance = (c.prev + 1..c.next)
+            .all(|k| segment_distance(&points[k], &points[c.prev], &points[c.next]) <= tolerance);
+        if !within_tolerance {
+            continue;
+        }
+        keep[c.index] = false;
+        next[c.prev] = c.next;
+        prev[c.next] = c.prev;
+        if c.prev > 0 {
+            heap.push(candidate(c.prev, prev[c.prev], c.next));
+        }
+        if c.next < n - 1 {
+            heap.push(candidate(c.next, c.prev, next[c.next]));
+        }
+    }
+}
+
+fn simplify_open(points: &[CoordinatePair], algorithm: Algorithm, tolerance: f64) -> Polyline {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    // RDP starts with only the end points and adds points, Visvalingam
+    // starts with all points and removes them
+    let mut keep = vec![algorithm == Algorithm::Visvalingam; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    match algorithm {
+        Algorithm::RamerDouglasPeucker => rdp(points, tolerance, &mut keep),
+        Algorithm::Visvalingam => visvalingam(points, tolerance, &mut keep),
+    }
+    points.iter().zip(keep).filter(|&(_, k)| k).map(|(p, _)| *p).collect()
+}
+
+/// Simplify a single polyline so that no original point deviates more than
+/// `tolerance` from the result.
+pub fn simplify(polyline: &[CoordinatePair], algorithm: Algorithm, tolerance: f64) -> Polyline {
+    if is_closed(polyline) {
+        // Split closed polylines at the point furthest from the start, so
+        // that the simplification of both halves is well defined
+        let split = (1..polyline.len() - 1)
+            .max_by(|&a, &b| distance(&polyline[0], &polyline[a]).total_cmp(&distance(&polyline[0], &polyline[b])))
+            .unwrap_or(0);
+        let mut result = simplify_open(&polyline[..split + 1], algorithm, tolerance);
+        result.pop();
+        result.extend(simplify_open(&polyline[split..], algorithm, tolerance));
+        result
+    } else {
+        simplify_open(polyline, algorithm, tolerance)
+    }
+}
+
+/// Simplify all polylines and return point count statistics.
+pub fn simplify_all(polylines: Vec<Polyline>, algorithm: Algorithm, tolerance: f64) -> (Vec<Polyline>, SimplifyStats) {
+    let points_before = polylines.iter().map(|p| p.len()).sum();
+    let simplified: Vec<Polyline> = polylines.iter()
+        .map(|p| simplify(p, algorithm, tolerance))
+        .collect();
+    let points_after = simplified.iter().map(|p| p.len()).sum();
+    (simplified, SimplifyStats { points_before, points_after })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    /// Maximum distance of any point of `original` to the `simplified` polyline.
+    fn max_deviation(original: &[CoordinatePair], simplified: &[CoordinatePair]) -> f64 {
+        original.iter()
+            .map(|p| simplified.windows(2)
+                .map(|w| segment_distance(p, &w[0], &w[1]))
+                .fold(f64::INFINITY, f64::min))
+            .fold(0.0, f64::max)
+    }
+
+    fn wave() -> Polyline {
+        (0..200).map(|i| {
+            let x = i as f64 / 10.0;
+            (x, x.sin() * 3.0).into()
+        }).collect()
+    }
+
+    #[test]
+    fn test_rdp() {
+        let line = polyline(&[(0.0, 0.0), (1.0, 0.05), (2.0, -0.05), (3.0, 1.0), (4.0, 0.0)]);
+        assert_eq!(
+            simplify(&line, Algorithm::RamerDouglasPeucker, 0.1),
+            polyline(&[(0.0, 0.0), (2.0, -0.05), (3.0, 1.0), (4.0, 0.0)]),
+        );
+    }
+
+    #[test]
+    fn test_visvalingam_collinear() {
+        let line = polyline(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 2.0)]);
+        assert_eq!(
+            simplify(&line, Algorithm::Visvalingam, 0.01),
+            polyline(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)]),
+        );
+    }
+
+    #[test]
+    fn test_max_deviation_guaranteed() {
+        let original = wave();
+        for &algorithm in &[Algorithm::RamerDouglasPeucker, Algorithm::Visvalingam] {
+            for &tolerance in &[0.01, 0.1, 0.5] {
+                let simplified = simplify(&original, algorithm, tolerance);
+                assert!(simplified.len() < original.len());
+                assert_eq!(simplified[0], original[0]);
+                assert_eq!(simplified[simplified.len() - 1], original[original.len() - 1]);
+                assert!(max_deviation(&original, &simplified) <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_closed_polyline_stays_closed() {
+        let circle: Polyline = (0..101).map(|i| {
+            let a = (i % 100) as f64 / 100.0 * 2.0 * ::std::f64::consts::PI;
+            (a.cos() * 10.0, a.sin() * 10.0).into()
+        }).collect();
+        for &algorithm in &[Algorithm::RamerDouglasPeucker, Algorithm::Visvalingam] {
+            let simplified = simplify(&circle, algorithm, 0.1);
+            assert!(simplified.len() < circle.len());
+            assert!(simplified.len() > 10);
+            assert!(is_closed(&simplified));
+            assert_eq!(simplified[0], circle[0]);
+            assert!(max_deviation(&circle, &simplified) <= 0.1);
+        }
+    }
+
+    #[test]
+    fn test_simplify_all_stats() {
+        let polylines = vec![wave(), polyline(&[(0.0, 0.0), (1.0, 1.0)])];
+        let (simplified, stats) = simplify_all(polylines, Algorithm::RamerDouglasPeucker, 0.1);
+        assert_eq!(simplified.len(), 2);
+        assert_eq!(stats.points_before, 202);
+        assert_eq!(stats.points_after, simplified[0].len() + 2);
+    }
+}
@@ -0,0 +1,272 @@
+//! Parsing of the SVG presentation attributes that svg2polylines cares about.
+
+use std::str;
+
+/// An RGB color as used in SVG `fill` and `stroke` attributes.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Return the color as a `#rrggbb` hex string.
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// Return the relative luminance of the color, from 0 (black) to 1
+    /// (white).
+    ///
+    /// The luminance is linear in the amount of reflected light, so that e.g.
+    /// hatching 50% of an area with black on white paper matches a color with
+    /// a luminance of 0.5.
+    pub fn luminance(&self) -> f64 {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+}
+
+/// The rule that determines which parts of a shape are inside, as used in
+/// the SVG `fill-rule` property.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum FillRule {
+    /// A point is inside if the winding number is not zero.
+    #[default]
+    NonZero,
+    /// A point is inside if the number of crossed edges is odd.
+    EvenOdd,
+}
+
+impl FillRule {
+    /// Whether a point with the given winding number is inside.
+    pub fn is_inside(&self, winding: i32) -> bool {
+        match *self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+impl str::FromStr for FillRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "nonzero" => Ok(FillRule::NonZero),
+            "evenodd" => Ok(FillRule::EvenOdd),
+            other => Err(format!("Invalid fill rule: {}", other)),
+        }
+    }
+}
+
+/// Basic named colors (the HTML 4 set plus a few common extras).
+const NAMED_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("black", (0, 0, 0)),
+    ("silver", (192, 192, 192)),
+    ("gray", (128, 128, 128)),
+    ("grey", (128, 128, 128)),
+    ("white", (255, 255, 255)),
+    ("maroon", (128, 0, 0)),
+    ("red", (255, 0, 0)),
+    ("purple", (128, 0, 128)),
+    ("fuchsia", (255, 0, 255)),
+    ("magenta", (255, 0, 255)),
+    ("green", (0, 128, 0)),
+    ("lime", (0, 255, 0)),
+    ("olive", (128, 128, 0)),
+    ("yellow", (255, 255, 0)),
+    ("navy", (0, 0, 128)),
+    ("blue", (0, 0, 255)),
+    ("teal", (0, 128, 128)),
+    ("aqua", (0, 255, 255)),
+    ("cyan", (0, 255, 255)),
+    ("orange", (255, 165, 0)),
+];
+
+impl str::FromStr for Color {
+    type Err = String;
+
+    /// Parse `#rgb`, `#rrggbb`, `rgb(r, g, b)` and basic named colors.
+    fn from_str(s: &str) -> Result<Color, String> {
+        let s = s.trim();
+        let invalid = || format!("Invalid color: {}", s);
+        if let Some(hex) = s.strip_prefix('#') {
+            let digits: Vec<u8> = hex.chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid)?;
+            match digits.len() {
+                3 => Ok(Color::new(digits[0] * 17, digits[1] * 17, digits[2] * 17)),
+                6 => Ok(Color::new(
+                    digits[0] * 16 + digits[1],
+                    digits[2] * 16 + digits[3],
+                    digits[4] * 16 + digits[5],
+                )),
+                _ => Err(invalid()),
+            }
+        } else if s.starts_with("rgb(") && s.ends_with(')') {
+            let channels = s[4..s.len() - 1]
+                .split(',')
+                .map(|c| {
+                    let c = c.trim();
+                    if let Some(percent) = c.strip_suffix('%') {
+                        percent.trim().parse::<f64>().map(|v| v * 255.0 / 100.0)
+                    } else {
+                        c.parse::<f64>()
+                    }
+                })
+                .map(|v| v.ok().map(|v| v.clamp(0.0, 255.0).round() as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid)?;
+            if channels.len() == 3 {
+                Ok(Color::new(channels[0], channels[1], channels[2]))
+            } else {
+                Err(invalid())
+            }
+        } else {
+            let lower = s.to_lowercase();
+            NAMED_COLORS.iter()
+                .find(|&&(name, _)| name == lower)
+                .map(|&(_, (r, g, b))| Color::new(r, g, b))
+                .ok_or_else(invalid)
+        }
+    }
+}
+
+/// Parse an SVG paint value (as used by `fill` and `stroke`).
+///
+/// Returns `Some(None)` for `none`, `Some(Some(color))` for a color and
+/// `None` if the value should be inherited or cannot be interpreted.
+///
+/// Paint servers like gradients and patterns (`url(#id)`) are not supported.
+/// They are replaced by the fallback color if one is given (as in
+/// `url(#gradient) red`), and are treated like `none` otherwise.
+pub(crate) fn parse_paint(value: &str) -> Option<Option<Color>> {
+    match value.trim() {
+        "none" | "transparent" => Some(None),
+        "inherit" | "currentColor" | "" => None,
+        v if v.starts_with("url(") => {
+            let fallback = v.find(')').map_or("", |end| v[end + 1..].trim());
+            if fallback.is_empty() { Some(None) } else { parse_paint(fallback) }
+        },
+        v => v.parse().ok().map(Some),
+    }
+}
+
+/// Parse an SVG opacity value, either a number or a percentage, clamped to
+/// the range from 0 to 1.
+pub(crate) fn parse_opacity(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let opacity = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok()? / 100.0,
+        None => value.parse::<f64>().ok()?,
+    };
+    Some(opacity.clamp(0.0, 1.0))
+}
+
+/// Parse an SVG `clip-path` value, a reference like `url(#id)` to a
+/// `<clipPath>` element.
+///
+/// Returns `Some(None)` for `none`, `Some(Some(id))` for a reference and
+/// `None` if the value should be inherited or cannot be interpreted.
+pub(crate) fn parse_clip_path(value: &str) -> Option<Option<String>> {
+    let value = value.trim();
+    if value == "none" {
+        return Some(None);
+    }
+    value.strip_prefix("url(")
+        .and_then(|v| v.strip_suffix(')'))
+        .map(|v| v.trim().trim_matches(|c| c == '"' || c == '\''))
+        .and_then(|v| v.strip_prefix('#'))
+        .map(|id| Some(id.to_string()))
+}
+
+/// Split the contents of a `style` attribute into `(property, value)` pairs.
+pub(crate) fn style_declarations(style: &str) -> Vec<(&str, &str)> {
+    style.split(';')
+        .filter_map(|decl| {
+            let mut parts = decl.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(prop), Some(value)) => Some((prop.trim(), value.trim())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!("#ff8000".parse(), Ok(Color::new(255, 128, 0)));
+        assert_eq!("#F80".parse(), Ok(Color::new(255, 136, 0)));
+        assert_eq!("rgb(10, 20,30)".parse(), Ok(Color::new(10, 20, 30)));
+        assert_eq!("rgb(100%, 0%, 50%)".parse(), Ok(Color::new(255, 0, 128)));
+        assert_eq!("Red".parse(), Ok(Color::new(255, 0, 0)));
+        assert!("#12345".parse::<Color>().is_err());
+        assert!("chartreuse-ish".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        assert_eq!("evenodd".parse(), Ok(FillRule::EvenOdd));
+        assert_eq!(" nonzero".parse(), Ok(FillRule::NonZero));
+        assert!("inherit".parse::<FillRule>().is_err());
+        assert!(FillRule::EvenOdd.is_inside(-1));
+        assert!(!FillRule::EvenOdd.is_inside(2));
+        assert!(FillRule::NonZero.is_inside(2));
+    }
+
+    #[test]
+    fn test_parse_paint() {
+        assert_eq!(parse_paint("none"), Some(None));
+        assert_eq!(parse_paint("#000"), Some(Some(Color::new(0, 0, 0))));
+        assert_eq!(parse_paint("inherit"), None);
+        assert_eq!(parse_paint("url(#gradient)"), Some(None));
+        assert_eq!(parse_paint("url('#gradient') #f00"), Some(Some(Color::new(255, 0, 0))));
+        assert_eq!(parse_paint("url(#pattern) none"), Some(None));
+    }
+
+    #[test]
+    fn test_parse_clip_path() {
+        assert_eq!(parse_clip_path("url(#page)"), Some(Some("page".to_string())));
+        assert_eq!(parse_clip_path(" url( '#page' ) "), Some(Some("page".to_string())));
+        assert_eq!(parse_clip_path("none"), Some(None));
+        assert_eq!(parse_clip_path("inherit"), None);
+    }
+
+    #[test]
+    fn test_luminance() {
+        assert_eq!(Color::new(0, 0, 0).luminance(), 0.0);
+        assert!((Color::new(255, 255, 255).luminance() - 1.0).abs() < 1e-9);
+        assert!((Color::new(188, 188, 188).luminance() - 0.5).abs() < 0.01);
+        assert!(Color::new(0, 255, 0).luminance() > Color::new(255, 0, 0).luminance());
+    }
+
+    #[test]
+    fn test_parse_opacity() {
+        assert_eq!(parse_opacity("0.5"), Some(0.5));
+        assert_eq!(parse_opacity(" 25% "), Some(0.25));
+        assert_eq!(parse_opacity("1.5"), Some(1.0));
+        assert_eq!(parse_opacity("inherit"), None);
+    }
+
+    #[test]
+    fn test_style_declarations() {
+        let decls = style_declarations("fill:none; stroke : #000000;;stroke-width:0.26");
+        assert_eq!(decls, vec![("fill", "none"), ("stroke", "#000000"), ("stroke-width", "0.26")]);
+    }
+}
@@ -0,0 +1,206 @@
+//! Holding tabs for cut contours.
+//!
+//! When a router cuts a part out of the stock, the part comes loose as soon
+//! as its outline is closed, and may move or be thrown around by the tool.
+//! Small uncut tabs (also called bridges) keep it attached until it is
+//! broken out by hand.
+//!
+//! Tabs are placed evenly along closed polylines, but moved away from
+//! corners, where they would be hard to clean up. They can be left out of
+//! the cut entirely, or returned as separate sections, e.g. to be cut with a
+//! raised tool in G-code.
+
+use {CoordinatePair, Polyline};
+use util::{closed_section, cumulative_lengths, is_closed};
+
+/// How many tabs to place on a closed polyline.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TabPlacement {
+    /// A fixed number of tabs on every closed polyline.
+    Count(usize),
+    /// Tabs about this far apart, but at least one per closed polyline.
+    Spacing(f64),
+}
+
+/// Options for holding tabs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TabOptions {
+    /// The length of every tab along the polyline.
+    pub width: f64,
+    pub placement: TabPlacement,
+    /// The minimal turn at a vertex, in degrees, to be considered a corner.
+    pub corner_angle: f64,
+    /// The minimal distance between a tab and a corner.
+    pub corner_clearance: f64,
+}
+
+impl Default for TabOptions {
+    fn default() -> Self {
+        TabOptions {
+            width: 3.0,
+            placement: TabPlacement::Count(4),
+            corner_angle: 30.0,
+            corner_clearance: 1.0,
+        }
+    }
+}
+
+/// A part of a polyline with tabs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Section {
+    pub polyline: Polyline,
+    /// Whether this part is a tab, i.e. must not be cut through.
+    pub tab: bool,
+}
+
+/// Return the distance of the corners of a closed polyline from its start.
+fn corners(polyline: &[CoordinatePair], lengths: &[f64], min_angle: f64) -> Vec<f64> {
+    let vertices = &polyline[..polyline.len() - 1];
+    let n = vertices.len();
+    (0..n)
+        .filter(|&i| {
+            let (previous, vertex, next) = (vertices[(i + n - 1) % n], vertices[i], vertices[(i + 1) % n]);
+            let (ax, ay) = (vertex.x - previous.x, vertex.y - previous.y);
+            let (bx, by) = (next.x - vertex.x, next.y - vertex.y);
+            let turn = (ax * by - ay * bx).atan2(ax * bx + ay * by).abs();
+            turn.to_degrees() >= min_angle
+        })
+        .map(|i| lengths[i])
+        .collect()
+}
+
+/// Return the distance of the tab centers from the start of a closed
+/// polyline, in ascending order.
+fn tab_centers(polyline: &[CoordinatePair], lengths: &[f64], options: &TabOptions) -> Vec<f64> {
+    let perimeter = lengths[lengths.len() - 1];
+    let count = match options.placement {
+        TabPlacement::Count(count) => count,
+        TabPlacement::Spacing(spacing) if spacing > 0.0 => ((perimeter / spacing).round() as usize).max(1),
+        TabPlacement::Spacing(_) => 0,
+    };
+    if options.width <= 0.0 || perimeter <= options.width {
+        return Vec::new();
+    }
+
+    let along = |a: f64, b: f64| {
+        let d = (a - b).rem_euclid(perimeter);
+        d.min(perimeter - d)
+    };
+    let corners = corners(polyline, lengths, options.corner_angle);
+    let clearance = options.width / 2.0 + options.corner_clearance;
+
+    let mut centers: Vec<f64> = Vec::with_capacity(count);
+    for k in 0..count {
+        // Move the ideal position to the closest position away from corners
+        // and other tabs
+        let ideal = (k as f64 + 0.5) * perimeter / count as f64;
+        let candidates = Some(ideal).into_iter()
+            .chain(corners.iter().flat_map(|&c| vec![c - clearance, c + clearance]))
+            .map(|s| s.rem_euclid(perimeter));
+        let best = candidates
+            .filter(|&s| corners.iter().all(|&c| along(s, c) >= clearance - 1e-9))
+            .filter(|&s| centers.iter().all(|&t| along(s, t) >= options.width))
+            .fold(None, |best: Option<f64>, s| match best {
+                Some(b) if along(b, ideal) <= along(s, ideal) => Some(b),
+                _ => Some(s),
+            });
+        if let Some(s) = best {
+            centers.push(s);
+        }
+    }
+    centers.sort_by(|a, b| a.total_cmp(b));
+    centers
+}
+
+/// Split a polyline into alternating cut and tab sections.
+///
+/// Only closed polylines get tabs, other polylines are returned as a single
+/// cut section. The sections of a closed polyline start and end at the end
+/// of a tab, so that they can be drawn in order.
+pub fn tab_sections(polyline: &[CoordinatePair], options: &TabOptions) -> Vec<Section> {
+    let whole = || vec![Section { polyline: polyline.to_vec(), tab: false }];
+    if !is_closed(polyline) {
+        return whole();
+    }
+    let lengths = cumulative_lengths(polyline);
+    let centers = tab_centers(polyline, &lengths, options);
+    if centers.is_empty() {
+        return whole();
+    }
+
+    let half = options.width / 2.0;
+    let mut sections = Vec::with_capacity(2 * centers.len());
+    for (i, &center) in centers.iter().enumerate() {
+        let next = centers.get(i + 1).cloned().unwrap_or(centers[0] + lengths[lengths.len() - 1]);
+        let cut = closed_section(polyline, &lengths, center + half, next - half);
+        // Adjacent tabs leave nothing to cut in between
+        if cut.len() > 2 || cut[0] != cut[1] {
+            sections.push(Section { polyline: cut, tab: false });
+        }
+        sections.push(Section { polyline: closed_section(polyline, &lengths, next - half, next + half), tab: true });
+    }
+    sections
+}
+
+/// Leave gaps for holding tabs in all closed polylines. Other polylines are
+/// returned unchanged.
+pub fn add_tabs(polylines: Vec<Polyline>, options: &TabOptions) -> Vec<Polyline> {
+    polylines.into_iter()
+        .flat_map(|polyline| {
+            if !is_closed(&polyline) {
+                return vec![polyline];
+            }
+            tab_sections(&polyline, options).into_iter()
+                .filter(|section| !section.tab)
+                .map(|section| section.polyline)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    fn square() -> Polyline {
+        polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)])
+    }
+
+    #[test]
+    fn test_tabs_on_edges() {
+        let options = TabOptions { width: 2.0, ..TabOptions::default() };
+        let open = polyline(&[(0.0, 0.0), (5.0, 5.0)]);
+        let result = add_tabs(vec![square(), open.clone()], &options);
+        assert_eq!(result, vec![
+            polyline(&[(6.0, 0.0), (10.0, 0.0), (10.0, 4.0)]),
+            polyline(&[(10.0, 6.0), (10.0, 10.0), (6.0, 10.0)]),
+            polyline(&[(4.0, 10.0), (0.0, 10.0), (0.0, 6.0)]),
+            polyline(&[(0.0, 4.0), (0.0, 0.0), (4.0, 0.0)]),
+            open,
+        ]);
+
+        let options = TabOptions { width: 2.0, placement: TabPlacement::Spacing(10.0), ..TabOptions::default() };
+        let sections = tab_sections(&square(), &options);
+        assert_eq!(sections.len(), 8);
+        assert_eq!(sections[1], Section { polyline: polyline(&[(10.0, 4.0), (10.0, 6.0)]), tab: true });
+        assert_eq!(sections[7], Section { polyline: polyline(&[(4.0, 0.0), (6.0, 0.0)]), tab: true });
+    }
+
+    #[test]
+    fn test_tabs_avoid_corners() {
+        // The only tab would be centered on the corner at (10, 10)
+        let options = TabOptions { width: 2.0, placement: TabPlacement::Count(1), ..TabOptions::default() };
+        let sections = tab_sections(&square(), &options);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[1], Section { polyline: polyline(&[(10.0, 7.0), (10.0, 9.0)]), tab: true });
+        assert_eq!(sections[0].polyline[0], (10.0, 9.0).into());
+
+        // No room for tabs between the corners
+        let options = TabOptions { width: 2.0, corner_clearance: 4.5, ..TabOptions::default() };
+        assert_eq!(add_tabs(vec![square()], &options), vec![square()]);
+    }
+}
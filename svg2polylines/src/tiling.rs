@@ -0,0 +1,169 @@
+//! Split large drawings into tiles to be plotted on separate pages.
+//!
+//! The drawing is covered with a grid of equally sized tiles, starting at the
+//! top left corner of its bounding box. Neighbouring tiles overlap, so that
+//! the pages can be glued or taped together, and every tile is returned with
+//! its own origin at its top left corner.
+//!
+//! Registration marks are small crosses at the same positions of the drawing
+//! on neighbouring tiles, inset from the corners of every tile by half the
+//! overlap, to line up the pages.
+
+use {CoordinatePair, Polyline};
+use clip::clip_to_rect;
+use util::bounds;
+
+/// Options for tiling.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileOptions {
+    /// The width of every tile, e.g. the width of the printable area of a
+    /// page.
+    pub width: f64,
+    /// The height of every tile.
+    pub height: f64,
+    /// The width of the strip shared by neighbouring tiles.
+    pub overlap: f64,
+    /// Whether to add registration marks to every tile.
+    pub registration_marks: bool,
+    /// The width and height of the registration marks.
+    pub mark_size: f64,
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        TileOptions {
+            width: 190.0,
+            height: 277.0,
+            overlap: 10.0,
+            registration_marks: false,
+            mark_size: 5.0,
+        }
+    }
+}
+
+/// A part of the drawing on a single page.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tile {
+    /// The row of the tile, counted from the top.
+    pub row: usize,
+    /// The column of the tile, counted from the left.
+    pub column: usize,
+    /// The position of the top left corner of the tile in the drawing.
+    pub origin: CoordinatePair,
+    /// The polylines of the drawing on this tile, relative to its origin.
+    pub polylines: Vec<Polyline>,
+    /// The registration marks on this tile, relative to its origin. Empty
+    /// unless enabled in the options.
+    pub marks: Vec<Polyline>,
+}
+
+/// Return the number of tiles with the given size and step needed to cover
+/// the given length.
+fn count(length: f64, size: f64, step: f64) -> usize {
+    (((length - size) / step).ceil().max(0.0) as usize) + 1
+}
+
+/// Split the polylines into tiles, in rows from top to bottom and from left
+/// to right within every row.
+///
+/// Returns an error if the overlap is not smaller than the tile size.
+pub fn tile(polylines: &[Polyline], options: &TileOptions) -> Result<Vec<Tile>, String> {
+    let step = (options.width - options.overlap, options.height - options.overlap);
+    if options.overlap < 0.0 || step.0 <= 0.0 || step.1 <= 0.0 {
+        return Err(format!(
+            "Invalid overlap {} for tiles of {} × {}", options.overlap, options.width, options.height,
+        ));
+    }
+    let (min, max) = match bounds(polylines.iter().flat_map(|p| p.iter())) {
+        Some(b) => b,
+        None => return Ok(Vec::new()),
+    };
+    let columns = count(max.x - min.x, options.width, step.0);
+    let rows = count(max.y - min.y, options.height, step.1);
+
+    // The registration marks of all tiles, in drawing coordinates
+    let half = options.mark_size / 2.0;
+    let inset = options.overlap / 2.0;
+    let marks: Vec<Polyline> = if options.registration_marks {
+        (0..rows + 1)
+            .flat_map(|row| (0..columns + 1).map(move |column| (row, column)))
+            .flat_map(|(row, column)| {
+                let x = min.x + column as f64 * step.0 + inset;
+                let y = min.y + row as f64 * step.1 + inset;
+                vec![
+                    vec![CoordinatePair::new(x - half, y), CoordinatePair::new(x + half, y)],
+                    vec![CoordinatePair::new(x, y - half), CoordinatePair::new(x, y + half)],
+                ]
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut tiles = Vec::with_capacity(rows * columns);
+    for row in 0..rows {
+        for column in 0..columns {
+            let origin = CoordinatePair::new(min.x + column as f64 * step.0, min.y + row as f64 * step.1);
+            let corner = CoordinatePair::new(origin.x + options.width, origin.y + options.height);
+            let relative = |polylines: Vec<Polyline>| -> Vec<Polyline> {
+                clip_to_rect(polylines, origin, corner).into_iter()
+                    .map(|p| p.iter().map(|q| CoordinatePair::new(q.x - origin.x, q.y - origin.y)).collect())
+                    .collect()
+            };
+            tiles.push(Tile {
+                row,
+                column,
+                origin,
+                polylines: relative(polylines.to_vec()),
+                marks: relative(marks.clone()),
+            });
+        }
+    }
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> Polyline {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn test_tile() {
+        let options = TileOptions { width: 20.0, height: 20.0, overlap: 10.0, ..TileOptions::default() };
+        let drawing = vec![polyline(&[(0.0, 5.0), (30.0, 5.0)])];
+        let tiles = tile(&drawing, &options).unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!((tiles[1].row, tiles[1].column), (0, 1));
+        assert_eq!(tiles[1].origin, (10.0, 5.0).into());
+        for tile in &tiles {
+            assert_eq!(tile.polylines, vec![polyline(&[(0.0, 0.0), (20.0, 0.0)])]);
+            assert!(tile.marks.is_empty());
+        }
+
+        let options = TileOptions { overlap: 20.0, ..options };
+        assert!(tile(&drawing, &options).is_err());
+    }
+
+    #[test]
+    fn test_registration_marks() {
+        let options = TileOptions {
+            width: 20.0,
+            height: 20.0,
+            overlap: 4.0,
+            registration_marks: true,
+            mark_size: 2.0,
+        };
+        let drawing = vec![polyline(&[(0.0, 0.0), (30.0, 30.0)])];
+        let tiles = tile(&drawing, &options).unwrap();
+        assert_eq!(tiles.len(), 4);
+        // Four crosses on every tile, inset by half the overlap
+        for tile in &tiles {
+            assert_eq!(tile.marks.len(), 8);
+            assert!(tile.marks.contains(&polyline(&[(1.0, 2.0), (3.0, 2.0)])));
+            assert!(tile.marks.contains(&polyline(&[(18.0, 17.0), (18.0, 19.0)])));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Affine transformations of coordinates.
+//!
+//! A `Transform` is a 2×3 matrix like the one of the SVG `matrix(a b c d e
+//! f)` transform, which maps `(x, y)` to `(a x + c y + e, b x + d y + f)`.
+//! It is used for the `transform` attribute of SVG elements while parsing,
+//! and can be used to move, scale, rotate or mirror the parsed polylines.
+//!
+//! Like in SVG, the y axis points down, so positive rotation angles turn
+//! clockwise on the screen.
+
+use std::ops;
+use std::str;
+
+use {CoordinatePair, Polyline};
+
+/// An affine transformation, see the module documentation.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Transform {
+    /// The identity transform.
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Transform { a, b, c, d, e, f }
+    }
+
+    /// The transform that keeps every point where it is.
+    pub fn identity() -> Self {
+        Transform::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translate(tx: f64, ty: f64) -> Self {
+        Transform::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    /// Scale around the origin.
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Transform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Rotate around the origin by an angle in degrees.
+    pub fn rotate(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Transform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Rotate around `center` by an angle in degrees.
+    pub fn rotate_around(degrees: f64, center: &CoordinatePair) -> Self {
+        Transform::translate(-center.x, -center.y)
+            .then(&Transform::rotate(degrees))
+            .then(&Transform::translate(center.x, center.y))
+    }
+
+    /// Skew along the x axis by an angle in degrees.
+    pub fn skew_x(degrees: f64) -> Self {
+        Transform::new(1.0, 0.0, degrees.to_radians().tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// Skew along the y axis by an angle in degrees.
+    pub fn skew_y(degrees: f64) -> Self {
+        Transform::new(1.0, degrees.to_radians().tan(), 0.0, 1.0, 0.0, 0.0)
+    }
+
+    /// Mirror at the y axis, i.e. negate the x coordinates.
+    pub fn mirror_x() -> Self {
+        Transform::scale(-1.0, 1.0)
+    }
+
+    /// Mirror at the x axis, i.e. negate the y coordinates.
+    pub fn mirror_y() -> Self {
+        Transform::scale(1.0, -1.0)
+    }
+
+    /// Return the transform that applies this transform first, and `other`
+    /// afterwards. This is the same as `*other * *self`.
+    pub fn then(&self, other: &Transform) -> Self {
+        *other * *self
+    }
+
+    /// Return the transform that undoes this transform, or `None` if it is
+    /// not invertible (e.g. a scale by zero).
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / determinant, -self.b / determinant, -self.c / determinant, self.a / determinant);
+        Some(Transform::new(a, b, c, d, -(a * self.e + c * self.f), -(b * self.e + d * self.f)))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    /// Apply the transform to a point.
+    pub fn apply(&self, p: &CoordinatePair) -> CoordinatePair {
+        CoordinatePair::new(
+            self.a * p.x + self.c * p.y + self.e,
+            self.b * p.x + self.d * p.y + self.f,
+        )
+    }
+
+    /// Apply the transform to every point of a polyline.
+    pub fn apply_polyline(&self, polyline: &[CoordinatePair]) -> Polyline {
+        polyline.iter().map(|p| self.apply(p)).collect()
+    }
+
+    /// Apply the transform to every point of the polylines.
+    pub fn apply_all(&self, mut polylines: Vec<Polyline>) -> Vec<Polyline> {
+        if !self.is_identity() {
+            for p in polylines.iter_mut().flat_map(|polyline| polyline.iter_mut()) {
+                *p = self.apply(p);
+            }
+        }
+        polylines
+    }
+}
+
+impl ops::Mul for Transform {
+    type Output = Transform;
+
+    /// The matrix product, which applies `other` first and `self`
+    /// afterwards, like in the SVG transform list `self other`.
+    fn mul(self, other: Transform) -> Transform {
+        Transform::new(
+            self.a * other.a + self.c * other.b,
+            self.b * other.a + self.d * other.b,
+            self.a * other.c + self.c * other.d,
+            self.b * other.c + self.d * other.d,
+            self.a * other.e + self.c * other.f + self.e,
+            self.b * other.e + self.d * other.f + self.f,
+        )
+    }
+}
+
+/// Split a list of numbers separated by whitespace and/or commas, where a
+/// sign also starts a new number (e.g. `1-2`).
+fn parse_numbers(s: &str) -> Option<Vec<f64>> {
+    let mut numbers = Vec::new();
+    let mut current = String::new();
+    for c in s.chars() {
+        let starts_number = (c == '-' || c == '+') && !current.is_empty()
+            && !current.ends_with('e') && !current.ends_with('E');
+        if (c.is_whitespace() || c == ',' || starts_number) && !current.is_empty() {
+            numbers.push(current.parse().ok()?);
+            current.clear();
+        }
+        if !c.is_whitespace() && c != ',' {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        numbers.push(current.parse().ok()?);
+    }
+    Some(numbers)
+}
+
+impl str::FromStr for Transform {
+    type Err = String;
+
+    /// Parse an SVG transform list like `translate(10, 20) rotate(45)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid transform: {}", s);
+        let mut transform = Transform::identity();
+        let mut rest = s.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        while !rest.is_empty() {
+            let open = rest.find('(').ok_or_else(invalid)?;
+            let close = rest.find(')').ok_or_else(invalid)?;
+            if close < open {
+                return Err(invalid());
+            }
+            let args = parse_numbers(&rest[open + 1..close]).ok_or_else(invalid)?;
+            let next = match (rest[..open].trim(), args.as_slice()) {
+                ("matrix", &[a, b, c, d, e, f]) => Transform::new(a, b, c, d, e, f),
+                ("translate", &[tx]) => Transform::translate(tx, 0.0),
+                ("translate", &[tx, ty]) => Transform::translate(tx, ty),
+                ("scale", &[s]) => Transform::scale(s, s),
+                ("scale", &[sx, sy]) => Transform::scale(sx, sy),
+                ("rotate", &[angle]) => Transform::rotate(angle),
+                ("rotate", &[angle, cx, cy]) => Transform::rotate_around(angle, &CoordinatePair::new(cx, cy)),
+                ("skewX", &[angle]) => Transform::skew_x(angle),
+                ("skewY", &[angle]) => Transform::skew_y(angle),
+                _ => return Err(invalid()),
+            };
+            transform = transform * next;
+            rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        }
+        Ok(transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::distance;
+
+    fn close(a: &CoordinatePair, b: (f64, f64)) -> bool {
+        distance(a, &b.into()) < 1e-9
+    }
+
+    #[test]
+    fn test_transforms() {
+        let p = CoordinatePair::new(1.0, 2.0);
+        assert_eq!(Transform::translate(3.0, 4.0).apply(&p), (4.0, 6.0).into());
+        assert_eq!(Transform::scale(2.0, -1.0).apply(&p), (2.0, -2.0).into());
+        assert_eq!(Transform::mirror_x().apply(&p), (-1.0, 2.0).into());
+        assert!(close(&Transform::rotate(90.0).apply(&p), (-2.0, 1.0)));
+        assert!(close(&Transform::rotate_around(180.0, &(1.0, 1.0).into()).apply(&p), (1.0, 0.0)));
+
+        // Scale first, then translate
+        let t = Transform::scale(2.0, 2.0).then(&Transform::translate(1.0, 0.0));
+        assert_eq!(t.apply(&p), (3.0, 4.0).into());
+        assert_eq!(t, Transform::translate(1.0, 0.0) * Transform::scale(2.0, 2.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let t = Transform::new(2.0, 1.0, -1.0, 3.0, 5.0, -7.0);
+        let inverse = t.inverse().unwrap();
+        let p = CoordinatePair::new(1.5, -2.5);
+        assert!(close(&inverse.apply(&t.apply(&p)), (1.5, -2.5)));
+        assert!(Transform::scale(0.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        assert_eq!("translate(10)".parse(), Ok(Transform::translate(10.0, 0.0)));
+        assert_eq!(
+            " translate(10,20) scale(2)".parse(),
+            Ok(Transform::translate(10.0, 20.0) * Transform::scale(2.0, 2.0)),
+        );
+        assert_eq!("matrix(1 0 0 1 -5-6)".parse(), Ok(Transform::translate(-5.0, -6.0)));
+        assert_eq!("scale(1e-1)".parse(), Ok(Transform::scale(0.1, 0.1)));
+        let t: Transform = "rotate(90, 10, 10)".parse().unwrap();
+        assert!(close(&t.apply(&(20.0, 10.0).into()), (10.0, 20.0)));
+        assert!("rotate(1, 2)".parse::<Transform>().is_err());
+        assert!("shear(1)".parse::<Transform>().is_err());
+    }
+}
@@ -0,0 +1,387 @@
+//! Small geometric helpers shared between the output and processing modules.
+
+use std::collections::HashMap;
+
+use CoordinatePair;
+
+/// A polyline is considered closed if it has at least three coordinate pairs
+/// and its last coordinate pair equals the first one.
+pub fn is_closed(line: &[CoordinatePair]) -> bool {
+    line.len() > 2 && line.first() == line.last()
+}
+
+/// Return a closed polyline that starts and ends at vertex `k` instead.
+pub fn rotate_closed(polyline: &[CoordinatePair], k: usize) -> Vec<CoordinatePair> {
+    let vertices = &polyline[..polyline.len() - 1];
+    let mut rotated = Vec::with_capacity(polyline.len());
+    rotated.extend_from_slice(&vertices[k..]);
+    rotated.extend_from_slice(&vertices[..k]);
+    rotated.push(vertices[k]);
+    rotated
+}
+
+/// Return the axis aligned bounding box `(min, max)` of the points, or `None`
+/// if there are no points.
+pub fn bounds<'a, I>(points: I) -> Option<(CoordinatePair, CoordinatePair)>
+    where I: IntoIterator<Item=&'a CoordinatePair>
+{
+    points.into_iter().fold(None, |acc, p| match acc {
+        None => Some((*p, *p)),
+        Some((min, max)) => Some((
+            CoordinatePair::new(min.x.min(p.x), min.y.min(p.y)),
+            CoordinatePair::new(max.x.max(p.x), max.y.max(p.y)),
+        )),
+    })
+}
+
+/// Euclidean distance between two points.
+pub fn distance(a: &CoordinatePair, b: &CoordinatePair) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+/// Return the distance along the polyline from its start to every vertex.
+pub fn cumulative_lengths(polyline: &[CoordinatePair]) -> Vec<f64> {
+    let mut total = 0.0;
+    let mut lengths = Vec::with_capacity(polyline.len());
+    for (i, p) in polyline.iter().enumerate() {
+        if i > 0 {
+            total += distance(&polyline[i - 1], p);
+        }
+        lengths.push(total);
+    }
+    lengths
+}
+
+/// Return the point at distance `s` along the polyline, given its cumulative
+/// lengths. Distances outside the polyline are clamped to its ends.
+pub fn point_along(polyline: &[CoordinatePair], lengths: &[f64], s: f64) -> CoordinatePair {
+    let i = lengths.partition_point(|&l| l <= s);
+    if i == 0 {
+        return polyline[0];
+    }
+    if i == lengths.len() {
+        return polyline[polyline.len() - 1];
+    }
+    let (a, b) = (polyline[i - 1], polyline[i]);
+    let t = (s - lengths[i - 1]) / (lengths[i] - lengths[i - 1]);
+    CoordinatePair::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+/// Return the part of a closed polyline from distance `start` to `end` along
+/// it, given its cumulative lengths. The part may wrap around the start of
+/// the polyline, i.e. `end` may be up to one perimeter larger than `start`.
+pub fn closed_section(polyline: &[CoordinatePair], lengths: &[f64], start: f64, end: f64) -> Vec<CoordinatePair> {
+    let perimeter = lengths[lengths.len() - 1];
+    let length = (end - start).clamp(0.0, perimeter);
+    let start = start.rem_euclid(perimeter);
+    let end = start + length;
+    let mut section = vec![point_along(polyline, lengths, start)];
+    for lap in 0..2 {
+        for (p, &l) in polyline.iter().zip(lengths).skip(1) {
+            let l = l + lap as f64 * perimeter;
+            if l > start && l < end {
+                section.push(*p);
+            }
+        }
+    }
+    section.push(point_along(polyline, lengths, if end > perimeter { end - perimeter } else { end }));
+    section
+}
+
+/// A uniform grid of points with associated values, used to find points
+/// within a small distance of a query point, or the nearest point overall.
+pub struct PointGrid<T> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<(CoordinatePair, T)>>,
+    /// The lowest and highest cell coordinates that points were inserted
+    /// into.
+    min_cell: (i64, i64),
+    max_cell: (i64, i64),
+}
+
+impl<T: Copy + PartialEq> PointGrid<T> {
+    /// Create a grid for queries with a radius of at most `cell_size`.
+    pub fn new(cell_size: f64) -> Self {
+        PointGrid {
+            cell_size: cell_size.max(1e-9),
+            cells: HashMap::new(),
+            min_cell: (i64::MAX, i64::MAX),
+            max_cell: (i64::MIN, i64::MIN),
+        }
+    }
+
+    fn cell(&self, p: &CoordinatePair) -> (i64, i64) {
+        ((p.x / self.cell_size).floor() as i64, (p.y / self.cell_size).floor() as i64)
+    }
+
+    pub fn insert(&mut self, p: CoordinatePair, value: T) {
+        let cell = self.cell(&p);
+        self.min_cell = (self.min_cell.0.min(cell.0), self.min_cell.1.min(cell.1));
+        self.max_cell = (self.max_cell.0.max(cell.0), self.max_cell.1.max(cell.1));
+        self.cells.entry(cell).or_default().push((p, value));
+    }
+
+    pub fn remove(&mut self, p: &CoordinatePair, value: T) {
+        let cell = self.cell(p);
+        if let Some(entries) = self.cells.get_mut(&cell) {
+            entries.retain(|&(_, v)| v != value);
+        }
+    }
+
+    /// Return the value of the point closest to `p` that is at most `radius`
+    /// away and matches `filter`. `radius` must not exceed the cell size.
+    pub fn nearest_within<F: Fn(T) -> bool>(&self, p: &CoordinatePair, radius: f64, filter: F) -> Option<T> {
+        let (cx, cy) = self.cell(p);
+        let mut best: Option<(f64, T)> = None;
+        for x in cx - 1..cx + 2 {
+            for y in cy - 1..cy + 2 {
+                for &(q, value) in self.cells.get(&(x, y)).map_or(&[][..], |v| &v[..]) {
+                    let d = distance(p, &q);
+                    let closer = match best {
+                        Some((bd, _)) => d < bd,
+                        None => true,
+                    };
+                    if d <= radius && closer && filter(value) {
+                        best = Some((d, value));
+                    }
+                }
+            }
+        }
+        best.map(|(_, value)| value)
+    }
+}
+
+impl<T: Copy + Ord> PointGrid<T> {
+    /// Return the value of the point closest to `p`, without a limit on the
+    /// distance. Ties are broken by the smallest value.
+    pub fn nearest(&self, p: &CoordinatePair) -> Option<T> {
+        let closer = |best: Option<(f64, T)>, d: f64, value: T| match best {
+            Some((bd, bv)) => d < bd || (d == bd && value < bv),
+            None => true,
+        };
+        let (cx, cy) = self.cell(p);
+        // Far outside of the grid (e.g. if all points coincide and the cells
+        // are tiny), searching ring by ring would take too long
+        if cx < self.min_cell.0 || cx > self.max_cell.0 || cy < self.min_cell.1 || cy > self.max_cell.1 {
+            let mut best = None;
+            for &(q, value) in self.cells.values().flat_map(|entries| entries.iter()) {
+                let d = distance(p, &q);
+                if closer(best, d, value) {
+                    best = Some((d, value));
+                }
+            }
+            return best.map(|(_, value)| value);
+        }
+        // Within the grid, no point is further away than this many rings
+        let max_ring = (cx - self.min_cell.0)
+            .max(self.max_cell.0 - cx)
+            .max(cy - self.min_cell.1)
+            .max(self.max_cell.1 - cy);
+        let mut best = None;
+        for ring in 0..max_ring + 1 {
+            for cell in ring_cells(cx, cy, ring) {
+                for &(q, value) in self.cells.get(&cell).map_or(&[][..], |v| &v[..]) {
+                    let d = distance(p, &q);
+                    if closer(best, d, value) {
+                        best = Some((d, value));
+                    }
+                }
+            }
+            // Cells in the next ring are at least `ring * cell_size` away
+            if best.is_some_and(|(d, _)| d <= ring as f64 * self.cell_size) {
+                break;
+            }
+        }
+        best.map(|(_, value)| value)
+    }
+}
+
+/// Return the cells on the border of the square with the given radius
+/// around `(cx, cy)`.
+fn ring_cells(cx: i64, cy: i64, ring: i64) -> Vec<(i64, i64)> {
+    if ring == 0 {
+        return vec![(cx, cy)];
+    }
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for d in -ring..ring + 1 {
+        cells.push((cx + d, cy - ring));
+        cells.push((cx + d, cy + ring));
+    }
+    for d in -ring + 1..ring {
+        cells.push((cx - ring, cy + d));
+        cells.push((cx + ring, cy + d));
+    }
+    cells
+}
+
+/// Return the point on the line segment from `a` to `b` closest to `p`.
+pub fn closest_point(p: &CoordinatePair, a: &CoordinatePair, b: &CoordinatePair) -> CoordinatePair {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return *a;
+    }
+    let t = (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_squared).clamp(0.0, 1.0);
+    CoordinatePair::new(a.x + t * dx, a.y + t * dy)
+}
+
+/// Distance of point `p` to the line segment from `a` to `b`.
+pub fn segment_distance(p: &CoordinatePair, a: &CoordinatePair, b: &CoordinatePair) -> f64 {
+    distance(p, &closest_point(p, a, b))
+}
+
+/// Return the parameters `(t, u)` of the intersection of the line through
+/// `a` and `b` with the line through `c` and `d`, such that the intersection
+/// is at `a + t * (b - a)` and at `c + u * (d - c)`. Returns `None` for
+/// parallel lines.
+pub fn intersection(a: &CoordinatePair, b: &CoordinatePair, c: &CoordinatePair, d: &CoordinatePair) -> Option<(f64, f64)> {
+    let cross = |ux: f64, uy: f64, vx: f64, vy: f64| ux * vy - uy * vx;
+    let denominator = cross(b.x - a.x, b.y - a.y, d.x - c.x, d.y - c.y);
+    if denominator == 0.0 {
+        return None;
+    }
+    let t = cross(c.x - a.x, c.y - a.y, d.x - c.x, d.y - c.y) / denominator;
+    let u = cross(c.x - a.x, c.y - a.y, b.x - a.x, b.y - a.y) / denominator;
+    Some((t, u))
+}
+
+/// Return the signed area of a closed polyline, positive if it is oriented
+/// counter-clockwise (in a coordinate system with the y axis pointing up).
+pub fn signed_area(line: &[CoordinatePair]) -> f64 {
+    line.windows(2)
+        .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+        .sum::<f64>() / 2.0
+}
+
+/// A line segment from the first to the second point.
+pub type Segment = (CoordinatePair, CoordinatePair);
+
+/// Return the x coordinates and directions (+1 upwards, -1 downwards) at
+/// which the segments cross the horizontal line at `y`.
+///
+/// Segments include their lower and exclude their upper end point, so that
+/// a line through a vertex is counted once.
+pub fn crossings<'a, I: Iterator<Item=&'a Segment>>(segments: I, y: f64) -> Vec<(f64, i32)> {
+    segments
+        .filter(|&&(a, b)| a.y != b.y && y >= a.y.min(b.y) && y < a.y.max(b.y))
+        .map(|&(a, b)| {
+            let x = a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y);
+            (x, if b.y > a.y { 1 } else { -1 })
+        })
+        .collect()
+}
+
+/// Return the winding number of the closed outlines formed by the segments
+/// around `p`.
+pub fn winding<'a, I: Iterator<Item=&'a Segment>>(segments: I, p: &CoordinatePair) -> i32 {
+    crossings(segments, p.y).into_iter()
+        .filter(|&(x, _)| x < p.x)
+        .map(|(_, direction)| direction)
+        .sum()
+}
+
+/// An index of segments by horizontal bands, to compute winding numbers
+/// without looking at every segment.
+pub struct WindingIndex<'a> {
+    segments: &'a [Segment],
+    min_y: f64,
+    band_height: f64,
+    bands: Vec<Vec<usize>>,
+}
+
+impl<'a> WindingIndex<'a> {
+    pub fn new(segments: &'a [Segment]) -> Self {
+        let (min_y, max_y) = segments.iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), s| {
+                (min.min(s.0.y).min(s.1.y), max.max(s.0.y).max(s.1.y))
+            });
+        let count = (segments.len() as f64).sqrt().ceil().max(1.0) as usize;
+        let band_height = ((max_y - min_y) / count as f64).max(1e-9);
+        let mut index = WindingIndex { segments, min_y, band_height, bands: vec![Vec::new(); count] };
+        for (i, s) in segments.iter().enumerate() {
+            let (first, last) = (index.band(s.0.y.min(s.1.y)), index.band(s.0.y.max(s.1.y)));
+            for band in &mut index.bands[first..last + 1] {
+                band.push(i);
+            }
+        }
+        index
+    }
+
+    fn band(&self, y: f64) -> usize {
+        (((y - self.min_y) / self.band_height).max(0.0) as usize).min(self.bands.len() - 1)
+    }
+
+    /// Return the winding number around `p`, see `winding`.
+    pub fn winding(&self, p: &CoordinatePair) -> i32 {
+        winding(self.bands[self.band(p.y)].iter().map(|&i| &self.segments[i]), p)
+    }
+}
+
+/// A uniform grid of line segments, used to find segments near a point or
+/// another segment.
+pub struct SegmentGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    segments: Vec<Segment>,
+}
+
+impl SegmentGrid {
+    /// Create a grid for queries with a radius of at most `radius`.
+    pub fn new(radius: f64) -> Self {
+        SegmentGrid {
+            cell_size: 2.0 * radius.max(1e-9),
+            cells: HashMap::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// The cells of points sampled along the segment, at most half a cell
+    /// size apart.
+    fn cells(&self, a: &CoordinatePair, b: &CoordinatePair) -> Vec<(i64, i64)> {
+        let steps = (2.0 * distance(a, b) / self.cell_size).ceil().max(1.0) as usize;
+        let mut cells: Vec<(i64, i64)> = (0..steps + 1)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                let (x, y) = (a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t);
+                ((x / self.cell_size).floor() as i64, (y / self.cell_size).floor() as i64)
+            })
+            .collect();
+        cells.dedup();
+        cells
+    }
+
+    /// Insert a segment and return its index.
+    pub fn insert(&mut self, a: CoordinatePair, b: CoordinatePair) -> usize {
+        let index = self.segments.len();
+        for cell in self.cells(&a, &b) {
+            let entries = self.cells.entry(cell).or_default();
+            if entries.last() != Some(&index) {
+                entries.push(index);
+            }
+        }
+        self.segments.push((a, b));
+        index
+    }
+
+    pub fn segment(&self, index: usize) -> Segment {
+        self.segments[index]
+    }
+
+    /// Return the indices of all segments that may come within the radius of
+    /// the segment from `a` to `b`, in ascending order.
+    pub fn near(&self, a: &CoordinatePair, b: &CoordinatePair) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for (cx, cy) in self.cells(a, b) {
+            for x in cx - 1..cx + 2 {
+                for y in cy - 1..cy + 2 {
+                    if let Some(entries) = self.cells.get(&(x, y)) {
+                        indices.extend_from_slice(entries);
+                    }
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+}